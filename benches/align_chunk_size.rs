@@ -0,0 +1,85 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+//! Measures the read-pair throughput of `align_fastq_pair_to_bam` with different
+//! `BwaSettings::set_chunk_size` batch sizes. Run with `cargo bench`.
+
+extern crate bwa;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use bwa::{BwaAligner, BwaReference, BwaSettings, PairedEndStats};
+
+const N_PAIRS: usize = 20000;
+const THREADS: usize = 4;
+
+fn revcomp(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|c| match *c {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            c => c,
+        })
+        .collect()
+}
+
+fn write_fastq(path: &Path, reads: &[Vec<u8>]) {
+    let mut out = BufWriter::new(File::create(path).unwrap());
+    let qual = vec![b'I'; 100];
+    for (i, read) in reads.iter().enumerate() {
+        writeln!(out, "@pair{}", i).unwrap();
+        out.write_all(read).unwrap();
+        out.write_all(b"\n+\n").unwrap();
+        out.write_all(&qual).unwrap();
+        out.write_all(b"\n").unwrap();
+    }
+}
+
+fn main() {
+    let fasta = std::fs::read_to_string("tests/test_ref.fa").unwrap();
+    let chr: Vec<u8> = fasta
+        .lines()
+        .skip_while(|l| *l != ">chr")
+        .skip(1)
+        .flat_map(|l| l.bytes())
+        .collect();
+
+    // forward-reverse pairs of 100bp reads with 300bp inserts, tiled along chr
+    let step = (chr.len() - 300) / N_PAIRS;
+    let (r1, r2): (Vec<_>, Vec<_>) = (0..N_PAIRS)
+        .map(|i| {
+            let frag = &chr[i * step..i * step + 300];
+            (frag[..100].to_vec(), revcomp(&frag[200..]))
+        })
+        .unzip();
+
+    let dir = std::env::temp_dir().join(format!("rust-bwa-chunk-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (fq1, fq2) = (dir.join("r1.fq"), dir.join("r2.fq"));
+    write_fastq(&fq1, &r1);
+    write_fastq(&fq2, &r2);
+
+    // each batch holds chunk_size * THREADS bases, so the smaller chunk size aligns the
+    // pairs in 10 batches, and the default in one
+    for &chunk_size in &[100_000, 10_000_000] {
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_chunk_size(chunk_size);
+        let aligner = BwaAligner::new(reference, settings, PairedEndStats::default());
+
+        let start = Instant::now();
+        let stats = aligner
+            .align_fastq_pair_to_bam(&fq1, &fq2, dir.join("out.bam"), THREADS)
+            .unwrap();
+        assert_eq!(stats.pairs, N_PAIRS as u64);
+
+        let rate = N_PAIRS as f64 / start.elapsed().as_secs_f64();
+        println!("chunk size {}: {:.0} pairs/s", chunk_size, rate);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
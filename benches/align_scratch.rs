@@ -0,0 +1,110 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+//! Compares the Rust heap allocations and time per read-pair of `align_read_pair` and
+//! `align_read_pair_into` with a reused `AlignScratch`. Allocations made by BWA itself
+//! aren't counted. Run with `cargo bench`.
+
+extern crate bwa;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use bwa::{AlignScratch, BwaAligner, ReadPair};
+
+const N_PAIRS: usize = 20000;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn revcomp(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|c| match *c {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            c => c,
+        })
+        .collect()
+}
+
+/// Run `align` on every pair, printing the allocations and time per pair
+fn measure<F: FnMut(&ReadPair)>(label: &str, pairs: &[ReadPair], mut align: F) {
+    let allocs_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for pair in pairs {
+        align(pair);
+    }
+    let elapsed = start.elapsed();
+    let allocs = ALLOCATIONS.load(Ordering::Relaxed) - allocs_before;
+
+    println!(
+        "{}: {:.1} allocations/pair, {:.1} us/pair",
+        label,
+        allocs as f64 / pairs.len() as f64,
+        elapsed.as_secs_f64() * 1e6 / pairs.len() as f64
+    );
+}
+
+fn main() {
+    let fasta = std::fs::read_to_string("tests/test_ref.fa").unwrap();
+    let chr: Vec<u8> = fasta
+        .lines()
+        .skip_while(|l| *l != ">chr")
+        .skip(1)
+        .flat_map(|l| l.bytes())
+        .collect();
+
+    // forward-reverse pairs of 100bp reads with 300bp inserts, tiled along chr
+    let step = (chr.len() - 300) / N_PAIRS;
+    let pairs: Vec<_> = (0..N_PAIRS)
+        .map(|i| {
+            let frag = &chr[i * step..i * step + 300];
+            ReadPair {
+                name: b"pair".to_vec(),
+                r1: frag[..100].to_vec(),
+                q1: vec![b'I'; 100],
+                r2: revcomp(&frag[200..]),
+                q2: vec![b'I'; 100],
+            }
+        })
+        .collect();
+
+    let aligner = BwaAligner::from_path("tests/test_ref.fa").unwrap();
+
+    measure("align_read_pair", &pairs, |p| {
+        aligner
+            .align_read_pair(&p.name, &p.r1, &p.q1, &p.r2, &p.q2)
+            .unwrap();
+    });
+
+    let mut scratch = AlignScratch::new();
+    let (mut out1, mut out2) = (Vec::new(), Vec::new());
+    measure("align_read_pair_into", &pairs, |p| {
+        aligner
+            .align_read_pair_into(&mut scratch, p, &mut out1, &mut out2)
+            .unwrap();
+    });
+}
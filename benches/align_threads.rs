@@ -0,0 +1,69 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+//! Measures how read-pair alignment throughput scales with the number of threads
+//! sharing a single `BwaAligner`. Run with `cargo bench`.
+
+extern crate bwa;
+
+use std::time::Instant;
+
+use bwa::BwaAligner;
+
+const N_PAIRS: usize = 20000;
+
+fn revcomp(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|c| match *c {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            c => c,
+        })
+        .collect()
+}
+
+fn main() {
+    let fasta = std::fs::read_to_string("tests/test_ref.fa").unwrap();
+    let chr: Vec<u8> = fasta
+        .lines()
+        .skip_while(|l| *l != ">chr")
+        .skip(1)
+        .flat_map(|l| l.bytes())
+        .collect();
+
+    // forward-reverse pairs of 100bp reads with 300bp inserts, tiled along chr
+    let step = (chr.len() - 300) / N_PAIRS;
+    let pairs: Vec<_> = (0..N_PAIRS)
+        .map(|i| {
+            let frag = &chr[i * step..i * step + 300];
+            (frag[..100].to_vec(), revcomp(&frag[200..]))
+        })
+        .collect();
+    let qual = vec![b'I'; 100];
+
+    let aligner = BwaAligner::from_path("tests/test_ref.fa").unwrap();
+
+    let mut single_thread_rate = None;
+    for &threads in &[1, 2, 4, 8] {
+        let start = Instant::now();
+        std::thread::scope(|scope| {
+            for chunk in pairs.chunks(N_PAIRS.div_ceil(threads)) {
+                let aligner = &aligner;
+                let qual = &qual;
+                scope.spawn(move || {
+                    for (r1, r2) in chunk {
+                        aligner
+                            .align_read_pair(b"pair", r1, qual, r2, qual)
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        let rate = N_PAIRS as f64 / start.elapsed().as_secs_f64();
+        let speedup = rate / *single_thread_rate.get_or_insert(rate);
+        println!("{} threads: {:.0} pairs/s ({:.2}x)", threads, rate, speedup);
+    }
+}
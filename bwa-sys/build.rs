@@ -1,3 +1,6 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
 // make -C bwa-sys/bwa/ -n libbwa.a | grep -o -E "[A-Za-z0-9_]+\.c"
 const FILES: &[&str] = &[
     "bwa/utils.c",
@@ -10,6 +13,12 @@ const FILES: &[&str] = &[
     "bwa/bwamem_pair.c",
     "bwa/bwamem_extra.c",
     "bwa/malloc_wrap.c",
+    "bwa/bwtindex.c",
+    "bwa/bwashm.c",
+    "bwa/bwt_gen.c",
+    "bwa/is.c",
+    "bwa/rope.c",
+    "bwa/rle.c",
 ];
 
 // make -C bwa-sys/bwa/ -nd libbwa.a | grep -o -E "[A-Za-z0-9_]+\.h" | sort | uniq
@@ -26,21 +35,115 @@ const HEADERS: &[&str] = &[
     "bwa/ksw.h",
     "bwa/kvec.h",
     "bwa/malloc_wrap.h",
+    "bwa/rle.h",
+    "bwa/rope.h",
     "bwa/utils.h",
 ];
 
+// the functions and variables of BWA that are bound, as in the bindgen command in the README
+#[cfg(feature = "bindgen")]
+const FUNCTIONS: &[&str] = &[
+    "mem_align1_core",
+    "mem_sam_pe",
+    "mem_opt_init",
+    "bwa_idx_build",
+    "bwa_idx_load",
+    "bwa_idx_destroy",
+    "bwa_idx2mem",
+    "bwa_mem2idx",
+    "bwa_idx_load_from_shm",
+    "bwa_shm_test",
+    "bwa_shm_stage",
+    "bwa_shm_destroy",
+    "mem_process_seqs",
+    "mem_align1",
+    "mem_approx_mapq_se",
+    "mem_pestat",
+    "mem_process_seq_pe",
+    "bwa_fill_scmat",
+    "bns_get_seq",
+    "bns_pos2rid",
+    "bwt_sa",
+    "smem_itr_init",
+    "smem_itr_destroy",
+    "smem_set_query",
+    "smem_next",
+];
+
+#[cfg(feature = "bindgen")]
+const VARS: &[&str] = &["bwa_verbose", "BWA_IDX_.*", "BWTALGO_.*", "MEM_F_.*"];
+
+/// Generate bindings for the BWA headers with bindgen
+#[cfg(feature = "bindgen")]
+fn write_bindings(out: &Path) {
+    let mut builder = bindgen::Builder::default()
+        .header("wrapper.h")
+        .generate_comments(false);
+    for f in FUNCTIONS {
+        builder = builder.whitelist_function(f);
+    }
+    for v in VARS {
+        builder = builder.whitelist_var(v);
+    }
+
+    builder
+        .generate()
+        .expect("couldn't generate BWA bindings")
+        .write_to_file(out)
+        .expect("couldn't write BWA bindings");
+}
+
+/// Use the bindings generated ahead of time, which are only valid on Linux
+#[cfg(not(feature = "bindgen"))]
+fn write_bindings(out: &Path) {
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if target_os != "linux" {
+        panic!(
+            "the prebuilt BWA bindings are for Linux only; enable the `bindgen` feature to \
+             generate bindings for {}",
+            target_os
+        );
+    }
+    std::fs::copy("linux_prebuilt_bindings.rs", out).expect("couldn't copy prebuilt BWA bindings");
+}
+
+/// The version of BWA in the submodule, from the `PACKAGE_VERSION` defined in main.c,
+/// which isn't compiled into the library
+fn bwa_version() -> String {
+    let main = std::fs::read_to_string("bwa/main.c").unwrap_or_default();
+    main.lines()
+        .find_map(|l| l.strip_prefix("#define PACKAGE_VERSION"))
+        .map(|v| v.trim().trim_matches('"').to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
 fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rerun-if-changed=linux_prebuilt_bindings.rs");
+    write_bindings(&out_dir.join("bindings.rs"));
+
+    println!("cargo:rerun-if-changed=bwa/main.c");
+    println!("cargo:rustc-env=BWA_VERSION={}", bwa_version());
+
     for file in FILES {
         println!("cargo:rerun-if-changed={}", file);
     }
     for file in HEADERS {
         println!("cargo:rerun-if-changed={}", file);
     }
+    println!("cargo:rerun-if-changed=shim.c");
     cc::Build::new()
         .define("COMPILATION_TIME_PLACE", "\"build.rs\"")
         .warnings(false)
         .extra_warnings(false)
         .files(FILES)
+        .file("shim.c")
         .flag("-fPIC")
         .compile("bwa");
+
+    // shm_open is in librt on older glibc
+    if env::var("CARGO_CFG_TARGET_OS").unwrap_or_default() == "linux" {
+        println!("cargo:rustc-link-lib=rt");
+    }
 }
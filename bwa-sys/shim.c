@@ -0,0 +1,12 @@
+/* Sizes of BWA's structs as seen by the C compiler, so the tests of the Rust bindings
+ * can check that their layouts match. */
+
+#include <stddef.h>
+#include "bwa/bwamem.h"
+
+size_t bwa_sys_sizeof_mem_opt_t(void) { return sizeof(mem_opt_t); }
+size_t bwa_sys_sizeof_mem_alnreg_t(void) { return sizeof(mem_alnreg_t); }
+size_t bwa_sys_sizeof_mem_pestat_t(void) { return sizeof(mem_pestat_t); }
+size_t bwa_sys_sizeof_bseq1_t(void) { return sizeof(bseq1_t); }
+size_t bwa_sys_sizeof_bntseq_t(void) { return sizeof(bntseq_t); }
+size_t bwa_sys_sizeof_bwaidx_t(void) { return sizeof(bwaidx_t); }
@@ -0,0 +1,313 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+//! A minimal FASTQ reader for feeding reads from disk to a `BwaAligner`.
+//! Gzip-compressed files are detected and decompressed automatically.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+
+use AlignError;
+
+/// Errors encountered while reading or aligning FASTQ files
+#[derive(Debug, thiserror::Error)]
+pub enum FastqError {
+    #[error("couldn't read FASTQ: {0}")]
+    Io(#[from] io::Error),
+    #[error("malformed FASTQ record at line {line}: {msg}")]
+    Malformed { line: usize, msg: String },
+    #[error("read 1 and read 2 FASTQs have different numbers of reads")]
+    UnpairedRead,
+    #[error("read 1 and read 2 of pair {pair} have different names: {r1} and {r2}")]
+    NameMismatch { pair: usize, r1: String, r2: String },
+    #[error("{0}")]
+    Align(#[from] AlignError),
+}
+
+/// A single FASTQ record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastqRecord {
+    /// Read name, without the leading `@`, the comment, or any `/1` or `/2` suffix
+    pub name: Vec<u8>,
+    pub seq: Vec<u8>,
+    pub qual: Vec<u8>,
+    /// The rest of the header line after the name and the whitespace following it,
+    /// if any. Appended to the records of the read by `BwaSettings::set_append_comment`.
+    pub comment: Option<Vec<u8>>,
+}
+
+/// Reads `FastqRecord`s from a buffered reader
+pub struct FastqReader<R> {
+    reader: R,
+    line: usize,
+    buf: Vec<u8>,
+}
+
+impl FastqReader<Box<dyn BufRead>> {
+    /// Open a FASTQ file, which may be gzip-compressed
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, FastqError> {
+        let mut file = BufReader::new(File::open(path)?);
+        let is_gzip = file.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+
+        let reader: Box<dyn BufRead> = if is_gzip {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(file)
+        };
+
+        Ok(FastqReader::new(reader))
+    }
+}
+
+impl<R: BufRead> FastqReader<R> {
+    pub fn new(reader: R) -> FastqReader<R> {
+        FastqReader {
+            reader,
+            line: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Read the next line into `self.buf`, without the line ending.
+    /// Returns false at the end of the input.
+    fn read_line(&mut self) -> Result<bool, FastqError> {
+        self.buf.clear();
+        if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+            return Ok(false);
+        }
+        self.line += 1;
+
+        if self.buf.ends_with(b"\n") {
+            self.buf.pop();
+            if self.buf.ends_with(b"\r") {
+                self.buf.pop();
+            }
+        }
+        Ok(true)
+    }
+
+    fn expect_line(&mut self, what: &str) -> Result<(), FastqError> {
+        if self.read_line()? {
+            Ok(())
+        } else {
+            Err(self.malformed(format!("missing {} line", what)))
+        }
+    }
+
+    fn malformed(&self, msg: String) -> FastqError {
+        FastqError::Malformed {
+            line: self.line,
+            msg,
+        }
+    }
+
+    /// Read the next record, or `None` at the end of the input
+    pub fn next_record(&mut self) -> Result<Option<FastqRecord>, FastqError> {
+        if !self.read_line()? {
+            return Ok(None);
+        }
+
+        if !self.buf.starts_with(b"@") {
+            return Err(self.malformed("header line doesn't start with '@'".to_string()));
+        }
+        let header = &self.buf[1..];
+        let name_len = header
+            .iter()
+            .position(|c| c.is_ascii_whitespace())
+            .unwrap_or(header.len());
+        let mut name = header[..name_len].to_vec();
+        // strip the read number, as bwa does
+        if name.ends_with(b"/1") || name.ends_with(b"/2") {
+            name.truncate(name.len() - 2);
+        }
+        // as with bwa, the comment is everything after the first whitespace character
+        let comment = match header.get(name_len + 1..) {
+            Some(comment) if !comment.is_empty() => Some(comment.to_vec()),
+            _ => None,
+        };
+
+        self.expect_line("sequence")?;
+        let seq = self.buf.clone();
+
+        self.expect_line("'+' separator")?;
+        if !self.buf.starts_with(b"+") {
+            return Err(self.malformed("separator line doesn't start with '+'".to_string()));
+        }
+
+        self.expect_line("quality")?;
+        if self.buf.len() != seq.len() {
+            return Err(self.malformed(format!(
+                "sequence has {} bases but quality has {}",
+                seq.len(),
+                self.buf.len()
+            )));
+        }
+        let qual = self.buf.clone();
+
+        Ok(Some(FastqRecord {
+            name,
+            seq,
+            qual,
+            comment,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for FastqReader<R> {
+    type Item = Result<FastqRecord, FastqError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(Some(rec)) => Some(Ok(rec)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Reads read-pairs from either a pair of FASTQ files, or a single interleaved FASTQ.
+/// The names of read 1 and read 2 of each pair must match, once any `/1` or `/2`
+/// suffix is removed.
+pub struct FastqPairReader {
+    r1: FastqReader<Box<dyn BufRead>>,
+    r2: Option<FastqReader<Box<dyn BufRead>>>,
+    pairs: usize,
+}
+
+impl FastqPairReader {
+    /// Read pairs from separate, uncompressed read 1 and read 2 readers
+    pub fn new<R1, R2>(r1: R1, r2: R2) -> FastqPairReader
+    where
+        R1: BufRead + 'static,
+        R2: BufRead + 'static,
+    {
+        FastqPairReader {
+            r1: FastqReader::new(Box::new(r1)),
+            r2: Some(FastqReader::new(Box::new(r2))),
+            pairs: 0,
+        }
+    }
+
+    /// Read pairs from separate read 1 and read 2 FASTQ files
+    pub fn from_paths<P: AsRef<Path>>(r1: P, r2: P) -> Result<FastqPairReader, FastqError> {
+        Ok(FastqPairReader {
+            r1: FastqReader::from_path(r1)?,
+            r2: Some(FastqReader::from_path(r2)?),
+            pairs: 0,
+        })
+    }
+
+    /// Read pairs from an interleaved FASTQ file, where each read 1 is followed by its read 2
+    pub fn interleaved<P: AsRef<Path>>(path: P) -> Result<FastqPairReader, FastqError> {
+        Ok(FastqPairReader {
+            r1: FastqReader::from_path(path)?,
+            r2: None,
+            pairs: 0,
+        })
+    }
+
+    /// Read the next pair, or `None` at the end of the input
+    pub fn next_pair(&mut self) -> Result<Option<(FastqRecord, FastqRecord)>, FastqError> {
+        let rec1 = self.r1.next_record()?;
+        let rec2 = match self.r2 {
+            Some(ref mut r2) => r2.next_record()?,
+            None if rec1.is_some() => self.r1.next_record()?,
+            None => None,
+        };
+
+        match (rec1, rec2) {
+            (Some(rec1), Some(rec2)) => {
+                self.pairs += 1;
+                if rec1.name != rec2.name {
+                    return Err(FastqError::NameMismatch {
+                        pair: self.pairs,
+                        r1: String::from_utf8_lossy(&rec1.name).into_owned(),
+                        r2: String::from_utf8_lossy(&rec2.name).into_owned(),
+                    });
+                }
+                Ok(Some((rec1, rec2)))
+            }
+            (None, None) => Ok(None),
+            _ => Err(FastqError::UnpairedRead),
+        }
+    }
+}
+
+impl Iterator for FastqPairReader {
+    type Item = Result<(FastqRecord, FastqRecord), FastqError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_pair() {
+            Ok(Some(pair)) => Some(Ok(pair)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_records() {
+        let fq = b"@read1/1 BX:Z:AC\tQT:Z:II\nACGT\n+\nIIII\n@read2\r\nGGC\r\n+read2\r\n#II\r\n";
+        let recs: Vec<_> = FastqReader::new(Cursor::new(&fq[..]))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(
+            recs,
+            vec![
+                FastqRecord {
+                    name: b"read1".to_vec(),
+                    seq: b"ACGT".to_vec(),
+                    qual: b"IIII".to_vec(),
+                    comment: Some(b"BX:Z:AC\tQT:Z:II".to_vec()),
+                },
+                FastqRecord {
+                    name: b"read2".to_vec(),
+                    seq: b"GGC".to_vec(),
+                    qual: b"#II".to_vec(),
+                    comment: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_records() {
+        let bad: &[&[u8]] = &[
+            b">read1\nACGT\n+\nIIII\n",
+            b"@read1\nACGT\n-\nIIII\n",
+            b"@read1\nACGT\n+\nIII\n",
+            b"@read1\nACGT\n+\n",
+        ];
+
+        for fq in bad {
+            let mut reader = FastqReader::new(Cursor::new(*fq));
+            match reader.next() {
+                Some(Err(FastqError::Malformed { .. })) => (),
+                res => panic!("expected malformed record error, got {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn mismatched_names() {
+        let r1: &[u8] = b"@read1/1\nACGT\n+\nIIII\n@read2/1\nACGT\n+\nIIII\n";
+        let r2: &[u8] = b"@read1/2\nACGT\n+\nIIII\n@read3/2\nACGT\n+\nIIII\n";
+        let mut pairs = FastqPairReader::new(Cursor::new(r1), Cursor::new(r2));
+
+        assert!(pairs.next().unwrap().is_ok());
+        match pairs.next() {
+            Some(Err(FastqError::NameMismatch { pair, r1, r2 })) => {
+                assert_eq!((pair, r1.as_str(), r2.as_str()), (2, "read2", "read3"))
+            }
+            res => panic!("expected name mismatch error, got {:?}", res),
+        }
+    }
+}
@@ -5,6 +5,8 @@
 //! back.
 //!
 //! ```
+//! # #[cfg(feature = "htslib")]
+//! # {
 //! use bwa::BwaAligner;
 //!
 //! let bwa = BwaAligner::from_path(&"tests/test_ref.fa").unwrap();
@@ -14,33 +16,155 @@
 //! let r2 = b"TGCTGCGTAGCAGATCGACCCAGGCATTCCCTAGCGTGCTCATGCTCTGGCTGGTAAACGCACGGATGAGGGCAAAAAT";
 //! let q2 = b"2222222222222222222222222222222222222222222222222222222222222222222222222222222";
 //!
-//! let (r1_alns, _r2_alns) = bwa.align_read_pair(b"read_name", r1, q1, r2, q2);
+//! let (r1_alns, _r2_alns) = bwa.align_read_pair(b"read_name", r1, q1, r2, q2).unwrap();
 //! println!("r1 mapping -- tid: {}, pos: {}", r1_alns[0].tid(), r1_alns[0].pos());
+//! # }
 //! ```
+//!
+//! With the default `htslib` feature disabled, `BwaAligner::align_read_pair_raw` returns
+//! the alignments as plain `SamRecordOwned`s instead.
 
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+extern crate bwa_sys;
 extern crate libc;
+#[cfg(feature = "htslib")]
 extern crate rust_htslib;
 
 extern crate thiserror;
 
-use std::ffi::{CStr, CString};
-use std::path::Path;
+#[macro_use]
+extern crate bitflags;
+extern crate flate2;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString, NulError};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
 use std::ptr;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
+#[cfg(feature = "htslib")]
+use fastq::{FastqError, FastqPairReader, FastqRecord};
+
+#[cfg(feature = "htslib")]
+use rust_htslib::bam;
+#[cfg(feature = "htslib")]
 use rust_htslib::bam::header::{Header, HeaderRecord};
-use rust_htslib::bam::record::Record;
+#[cfg(feature = "htslib")]
+use rust_htslib::bam::record::{Aux, Record};
+#[cfg(feature = "htslib")]
 use rust_htslib::bam::HeaderView;
 
+pub mod fastq;
+mod raw;
+#[cfg(feature = "htslib")]
+pub mod record;
+#[cfg(feature = "htslib")]
+mod sam;
+
+pub use bwa_sys::{
+    mem_opt_t, MEM_F_ALL, MEM_F_KEEP_SUPP_MAPQ, MEM_F_NOPAIRING, MEM_F_NO_MULTI, MEM_F_NO_RESCUE,
+    MEM_F_PE, MEM_F_PRIMARY5, MEM_F_REF_HDR, MEM_F_SMARTPE, MEM_F_SOFTCLIP,
+};
+pub use raw::SamRecordOwned;
+#[cfg(feature = "htslib")]
+pub use record::{parse_sa, parse_xa, AltHit, AuxValue, OwnedAlignment};
+
 // include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
-/// BWA settings object. Currently only default settings are enabled
+/// Set the verbosity of the messages BWA writes to stderr: 0 for none, 1 for errors,
+/// 2 for warnings, 3 (the default) for progress messages, and 4 or more for debugging.
+/// This is global to BWA, so it affects every `BwaReference` and `BwaAligner`,
+/// including loading references. Fatal errors that abort the process are always written.
+pub fn set_verbosity(level: u8) {
+    unsafe {
+        bwa_sys::bwa_verbose = level as i32;
+    }
+}
+
+/// The version of the bundled BWA, e.g. `0.7.17-r1188`
+pub fn version() -> &'static str {
+    bwa_sys::bwa_version()
+}
+
+/// All the parameters of a `BwaSettings`, named as in BWA's `mem_opt_t`, for logging
+/// or storing the parameters of an alignment run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BwaParams {
+    pub a: i32,
+    pub b: i32,
+    pub o_del: i32,
+    pub e_del: i32,
+    pub o_ins: i32,
+    pub e_ins: i32,
+    pub pen_unpaired: i32,
+    pub pen_clip5: i32,
+    pub pen_clip3: i32,
+    pub w: i32,
+    pub zdrop: i32,
+    pub max_mem_intv: u64,
+    pub T: i32,
+    pub flag: i32,
+    pub min_seed_len: i32,
+    pub min_chain_weight: i32,
+    pub max_chain_extend: i32,
+    pub split_factor: f32,
+    pub split_width: i32,
+    pub max_occ: i32,
+    pub max_chain_gap: i32,
+    pub n_threads: i32,
+    pub chunk_size: i32,
+    pub mask_level: f32,
+    pub drop_ratio: f32,
+    pub XA_drop_ratio: f32,
+    pub mask_level_redun: f32,
+    pub mapQ_coef_len: f32,
+    pub mapQ_coef_fac: i32,
+    pub max_ins: i32,
+    pub max_matesw: i32,
+    pub max_XA_hits: i32,
+    pub max_XA_hits_alt: i32,
+    /// The scoring matrix, if it isn't the one derived from the match score `a` and
+    /// mismatch penalty `b`, e.g. after `BwaSettings::set_score_matrix` or `set_n_penalty`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mat: Option<[i8; 25]>,
+    /// Append FASTQ comments to the output (bwa mem -C). Not part of `mem_opt_t`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub append_comment: bool,
+}
+
+/// Presets of BWA settings for different types of reads (bwa mem -x)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// PacBio reads to a reference
+    PacBio,
+    /// Oxford Nanopore 2D reads to a reference
+    Ont2d,
+    /// Intra-species contigs to a reference
+    IntraCtg,
+}
+
+/// BWA settings object. Currently only default settings are enabled.
+/// `mem_opt_t` is plain data, so settings can be cloned to derive variants of a base
+/// configuration. The setters only check their values in debug builds; use
+/// `BwaSettingsBuilder` to validate settings in release builds too.
+#[derive(Clone)]
 pub struct BwaSettings {
     bwa_settings: bwa_sys::mem_opt_t,
+    append_comment: bool,
 }
 
 impl BwaSettings {
@@ -49,10 +173,170 @@ impl BwaSettings {
         let ptr = unsafe { bwa_sys::mem_opt_init() };
         let bwa_settings = unsafe { *ptr };
         unsafe { libc::free(ptr as *mut libc::c_void) };
-        BwaSettings { bwa_settings }
+        BwaSettings {
+            bwa_settings,
+            append_comment: false,
+        }
+    }
+
+    /// Create a `BwaSettings` object with the default BWA parameters adjusted by
+    /// `preset`, exactly as `bwa mem -x` does. Other settings may then be changed
+    /// with the setters as usual.
+    pub fn preset(preset: Preset) -> BwaSettings {
+        let mut settings = BwaSettings::new();
+        {
+            let s = &mut settings.bwa_settings;
+            match preset {
+                Preset::IntraCtg => {
+                    s.o_del = 16;
+                    s.o_ins = 16;
+                    s.b = 9;
+                    s.pen_clip5 = 5;
+                    s.pen_clip3 = 5;
+                }
+                Preset::PacBio | Preset::Ont2d => {
+                    s.o_del = 1;
+                    s.e_del = 1;
+                    s.o_ins = 1;
+                    s.e_ins = 1;
+                    s.b = 1;
+                    s.split_factor = 10.0;
+                    s.pen_clip5 = 0;
+                    s.pen_clip3 = 0;
+                    if preset == Preset::Ont2d {
+                        s.min_chain_weight = 20;
+                        s.min_seed_len = 14;
+                    } else {
+                        s.min_chain_weight = 40;
+                        s.min_seed_len = 17;
+                    }
+                }
+            }
+
+            unsafe {
+                bwa_sys::bwa_fill_scmat(s.a, s.b, s.mat.as_mut_ptr());
+            }
+        }
+        settings
+    }
+
+    /// The parameters of these settings
+    pub fn params(&self) -> BwaParams {
+        let s = &self.bwa_settings;
+        BwaParams {
+            a: s.a,
+            b: s.b,
+            o_del: s.o_del,
+            e_del: s.e_del,
+            o_ins: s.o_ins,
+            e_ins: s.e_ins,
+            pen_unpaired: s.pen_unpaired,
+            pen_clip5: s.pen_clip5,
+            pen_clip3: s.pen_clip3,
+            w: s.w,
+            zdrop: s.zdrop,
+            max_mem_intv: s.max_mem_intv,
+            T: s.T,
+            flag: s.flag,
+            min_seed_len: s.min_seed_len,
+            min_chain_weight: s.min_chain_weight,
+            max_chain_extend: s.max_chain_extend,
+            split_factor: s.split_factor,
+            split_width: s.split_width,
+            max_occ: s.max_occ,
+            max_chain_gap: s.max_chain_gap,
+            n_threads: s.n_threads,
+            chunk_size: s.chunk_size,
+            mask_level: s.mask_level,
+            drop_ratio: s.drop_ratio,
+            XA_drop_ratio: s.XA_drop_ratio,
+            mask_level_redun: s.mask_level_redun,
+            mapQ_coef_len: s.mapQ_coef_len,
+            mapQ_coef_fac: s.mapQ_coef_fac,
+            max_ins: s.max_ins,
+            max_matesw: s.max_matesw,
+            max_XA_hits: s.max_XA_hits,
+            max_XA_hits_alt: s.max_XA_hits_alt,
+            mat: if s.mat == score_matrix(s.a, s.b) {
+                None
+            } else {
+                Some(s.mat)
+            },
+            append_comment: self.append_comment,
+        }
+    }
+
+    /// Create a `BwaSettings` object from a set of parameters, e.g. from `params`.
+    /// The parameters aren't validated, see `BwaSettingsBuilder`.
+    pub fn from_params(params: &BwaParams) -> BwaSettings {
+        let mut settings = BwaSettings::new();
+        {
+            let s = &mut settings.bwa_settings;
+            s.a = params.a;
+            s.b = params.b;
+            s.o_del = params.o_del;
+            s.e_del = params.e_del;
+            s.o_ins = params.o_ins;
+            s.e_ins = params.e_ins;
+            s.pen_unpaired = params.pen_unpaired;
+            s.pen_clip5 = params.pen_clip5;
+            s.pen_clip3 = params.pen_clip3;
+            s.w = params.w;
+            s.zdrop = params.zdrop;
+            s.max_mem_intv = params.max_mem_intv;
+            s.T = params.T;
+            s.flag = params.flag;
+            s.min_seed_len = params.min_seed_len;
+            s.min_chain_weight = params.min_chain_weight;
+            s.max_chain_extend = params.max_chain_extend;
+            s.split_factor = params.split_factor;
+            s.split_width = params.split_width;
+            s.max_occ = params.max_occ;
+            s.max_chain_gap = params.max_chain_gap;
+            s.n_threads = params.n_threads;
+            s.chunk_size = params.chunk_size;
+            s.mask_level = params.mask_level;
+            s.drop_ratio = params.drop_ratio;
+            s.XA_drop_ratio = params.XA_drop_ratio;
+            s.mask_level_redun = params.mask_level_redun;
+            s.mapQ_coef_len = params.mapQ_coef_len;
+            s.mapQ_coef_fac = params.mapQ_coef_fac;
+            s.max_ins = params.max_ins;
+            s.max_matesw = params.max_matesw;
+            s.max_XA_hits = params.max_XA_hits;
+            s.max_XA_hits_alt = params.max_XA_hits_alt;
+            s.mat = params.mat.unwrap_or_else(|| score_matrix(s.a, s.b));
+        }
+        settings.append_comment = params.append_comment;
+        settings
+    }
+
+    /// Create a `BwaSettings` object from a complete `mem_opt_t`, as built by hand or
+    /// taken from `as_raw`. For experts: nothing is validated, and BWA may misbehave or
+    /// crash with inconsistent settings.
+    pub fn from_raw(opt: mem_opt_t) -> BwaSettings {
+        BwaSettings {
+            bwa_settings: opt,
+            append_comment: false,
+        }
     }
 
-    /// Set alignment scores
+    /// The underlying BWA `mem_opt_t` of these settings
+    pub fn as_raw(&self) -> &mem_opt_t {
+        &self.bwa_settings
+    }
+
+    /// Change the underlying BWA `mem_opt_t` directly, for parameters that have no setter.
+    /// For experts: nothing is validated, and BWA may misbehave or crash with inconsistent
+    /// settings. The score matrix `mat` isn't refilled, so changing the match score `a` or
+    /// mismatch penalty `b` here doesn't change the scores of alignments; use `set_scores`.
+    pub fn modify_raw<F: FnOnce(&mut mem_opt_t)>(&mut self, f: F) {
+        f(&mut self.bwa_settings);
+    }
+
+    /// Set alignment scores.
+    /// Panics in debug builds if `matchp` or `gap_extend` isn't positive, or `mismatch` or
+    /// `gap_open` is negative.
     pub fn set_scores(
         mut self,
         matchp: i32,
@@ -60,6 +344,10 @@ impl BwaSettings {
         gap_open: i32,
         gap_extend: i32,
     ) -> BwaSettings {
+        debug_check(positive("match score", matchp));
+        debug_check(non_negative("mismatch penalty", mismatch));
+        debug_check(non_negative("gap open penalty", gap_open));
+        debug_check(positive("gap extension penalty", gap_extend));
         self.bwa_settings.a = matchp;
         self.bwa_settings.b = mismatch;
         self.bwa_settings.o_del = gap_open;
@@ -73,281 +361,4353 @@ impl BwaSettings {
         self
     }
 
-    /// Set clipping score penalties
+    /// Set the gap open and extension penalties of deletions and insertions separately
+    /// (bwa mem -O and -E), rather than the same penalties for both as with `set_scores`.
+    /// A gap of length `k` costs `open + k * extend`.
+    /// Panics in debug builds if `o_del` or `o_ins` is negative, or `e_del` or `e_ins`
+    /// isn't positive.
+    pub fn set_gap_penalties(
+        mut self,
+        o_del: i32,
+        e_del: i32,
+        o_ins: i32,
+        e_ins: i32,
+    ) -> BwaSettings {
+        debug_check(non_negative("deletion open penalty", o_del));
+        debug_check(positive("deletion extension penalty", e_del));
+        debug_check(non_negative("insertion open penalty", o_ins));
+        debug_check(positive("insertion extension penalty", e_ins));
+        self.bwa_settings.o_del = o_del;
+        self.bwa_settings.e_del = e_del;
+        self.bwa_settings.o_ins = o_ins;
+        self.bwa_settings.e_ins = e_ins;
+        self
+    }
+
+    /// Set the substitution scores directly, rather than from a single match score and
+    /// mismatch penalty as with `set_scores`. `mat[5 * r + q]` is the score of aligning
+    /// read base `q` to reference base `r`, with bases numbered A, C, G, T, N from 0, so
+    /// the matrix needn't be symmetric -- e.g. to make reference C to read T substitutions
+    /// cheap for bisulfite-converted reads. BWA still uses the match score and mismatch
+    /// penalty of `set_scores` when computing mapping qualities, and `set_scores` replaces
+    /// the matrix.
+    pub fn set_score_matrix(mut self, mat: [i8; 25]) -> BwaSettings {
+        self.bwa_settings.mat = mat;
+        self
+    }
+
+    /// Set the penalty for aligning an N, in either the read or the reference, to any base,
+    /// by filling the N row and column (index 4) of the score matrix with `-penalty`. BWA's
+    /// default is 1. Lowering it lets reads with many Ns reach the minimum output score,
+    /// though BWA never seeds on Ns, so a read still needs a stretch of at least the minimum
+    /// seed length without them to align. `set_scores` and `set_score_matrix` replace the
+    /// whole matrix, so call this after them.
+    /// Panics in debug builds if `penalty` is negative.
+    pub fn set_n_penalty(mut self, penalty: i8) -> BwaSettings {
+        debug_check(non_negative("N penalty", i32::from(penalty)));
+        for i in 0..5 {
+            self.bwa_settings.mat[i * 5 + 4] = -penalty;
+            self.bwa_settings.mat[4 * 5 + i] = -penalty;
+        }
+        self
+    }
+
+    /// Set clipping score penalties.
+    /// Panics in debug builds if `clip5` or `clip3` is negative.
     pub fn set_clip_scores(mut self, clip5: i32, clip3: i32) -> BwaSettings {
+        debug_check(non_negative("5' clipping penalty", clip5));
+        debug_check(non_negative("3' clipping penalty", clip3));
         self.bwa_settings.pen_clip5 = clip5;
         self.bwa_settings.pen_clip3 = clip3;
         self
     }
 
-    /// Set unpaired read penalty
+    /// Set the penalty for placing the reads of a pair without pairing them (bwa mem -U).
+    /// A read's hits that score more than this below its best hit aren't considered for
+    /// pairing. The default is 17. As for every parameter, the current value is given by
+    /// `params`.
+    /// Panics in debug builds if `unpaired` is negative.
     pub fn set_unpaired(mut self, unpaired: i32) -> BwaSettings {
+        debug_check(non_negative("unpaired penalty", unpaired));
         self.bwa_settings.pen_unpaired = unpaired;
         self
     }
 
-    /// Mark shorter splits as secondary
-    pub fn set_no_multi(mut self) -> BwaSettings {
-        self.bwa_settings.flag |= 0x10; // MEM_F_NO_MULTI
+    /// Set minimum alignment score for a record to be output (bwa mem -T).
+    /// Reads without a sufficiently high-scoring alignment are reported as unmapped.
+    /// Panics in debug builds if `min_score` is negative.
+    pub fn set_min_output_score(mut self, min_score: i32) -> BwaSettings {
+        debug_check(non_negative("minimum output score", min_score));
+        self.bwa_settings.T = min_score;
         self
     }
-}
-
-#[derive(Debug, thiserror::Error)]
-#[error("{0}")]
-pub struct ReferenceError(String);
-
-/// A BWA reference object to perform alignments to.
-/// Must be loaded from a BWA index created with `bwa index`
-pub struct BwaReference {
-    bwt_data: *const bwa_sys::bwaidx_t,
-    contig_names: Vec<String>,
-    contig_lengths: Vec<usize>,
-}
-unsafe impl Sync for BwaReference {}
-
-impl BwaReference {
-    /// Load a BWA reference from disk. Pass the fasta filename of the
-    /// original reference as `path`
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<BwaReference, ReferenceError> {
-        let idx_file = CString::new(path.as_ref().to_str().unwrap()).unwrap();
-        let idx = unsafe { bwa_sys::bwa_idx_load(idx_file.as_ptr(), 0x7 as i32) }; // FIXME -- use BWA_IDX_ALL
-
-        if idx.is_null() {
-            return Err(ReferenceError(format!(
-                "couldn't load reference: {:?}",
-                path.as_ref()
-            )));
-        }
 
-        let mut contig_names = Vec::new();
-        let mut contig_lengths = Vec::new();
-        let num_contigs = unsafe { (*(*idx).bns).n_seqs };
+    /// Set band width for banded alignment (bwa mem -w). Gaps longer than
+    /// the band width can't be spanned by a single alignment. BWA retries an extension
+    /// that reaches the edge of the band with the band doubled, so gaps up to twice the
+    /// band width may be aligned. The default is 100.
+    /// Panics in debug builds if `band_width` isn't positive.
+    pub fn set_band_width(mut self, band_width: i32) -> BwaSettings {
+        debug_check(positive("band width", band_width));
+        self.bwa_settings.w = band_width;
+        self
+    }
 
-        for i in 0..num_contigs as isize {
-            unsafe {
-                let name = CStr::from_ptr((*(*(*idx).bns).anns.offset(i)).name);
-                let sz = (*(*(*idx).bns).anns.offset(i)).len;
+    /// Set the largest insert size considered when inferring paired-end stats with
+    /// `PairedEndStats::infer`. Pairs with larger inserts are ignored.
+    /// Panics in debug builds if `max_insert` isn't positive.
+    pub fn set_max_insert(mut self, max_insert: i32) -> BwaSettings {
+        debug_check(positive("max insert size", max_insert));
+        self.bwa_settings.max_ins = max_insert;
+        self
+    }
 
-                let name_string = name.to_owned().into_string().unwrap();
-                contig_names.push(name_string);
-                contig_lengths.push(sz as usize)
-            }
-        }
+    /// Set off-diagonal X-dropoff for alignment extension (bwa mem -d).
+    /// Panics in debug builds if `zdrop` isn't positive.
+    pub fn set_zdrop(mut self, zdrop: i32) -> BwaSettings {
+        debug_check(positive("zdrop", zdrop));
+        self.bwa_settings.zdrop = zdrop;
+        self
+    }
 
-        Ok(BwaReference {
-            bwt_data: idx,
-            contig_names,
-            contig_lengths,
-        })
+    /// Set minimum seed length (bwa mem -k).
+    /// Panics in debug builds if `min_seed_len` isn't positive.
+    pub fn set_min_seed_len(mut self, min_seed_len: i32) -> BwaSettings {
+        debug_check(positive("minimum seed length", min_seed_len));
+        self.bwa_settings.min_seed_len = min_seed_len;
+        self
     }
 
-    pub fn create_bam_header(&self) -> Header {
-        let mut header = Header::new();
-        self.populate_bam_header(&mut header);
-        header
+    /// Set re-seeding trigger: look for internal seeds inside seeds longer
+    /// than `split_factor * min_seed_len` (bwa mem -r).
+    /// Panics in debug builds if `split_factor` isn't positive.
+    pub fn set_split_factor(mut self, split_factor: f32) -> BwaSettings {
+        debug_check(positive("split factor", split_factor));
+        self.bwa_settings.split_factor = split_factor;
+        self
     }
 
-    pub fn populate_bam_header(&self, header: &mut Header) {
-        for (ref contig_name, &len) in self.contig_names.iter().zip(self.contig_lengths.iter()) {
-            add_ref_to_bam_header(header, &contig_name, len);
-        }
+    /// Set the chain drop ratio: chains shorter than `drop_ratio` times the longest
+    /// overlapping chain are dropped, and with `set_output_all`, secondary alignments
+    /// scoring less than `drop_ratio` times the primary aren't output (bwa mem -D).
+    /// The default is 0.5.
+    /// Panics in debug builds if `drop_ratio` isn't between 0 and 1.
+    pub fn set_drop_ratio(mut self, drop_ratio: f32) -> BwaSettings {
+        debug_check(fraction("drop ratio", drop_ratio));
+        self.bwa_settings.drop_ratio = drop_ratio;
+        self
     }
-}
 
-impl Drop for BwaReference {
-    fn drop(&mut self) {
-        unsafe {
-            bwa_sys::bwa_idx_destroy(self.bwt_data as *mut bwa_sys::bwaidx_t);
-        }
+    /// Set the maximum number of alternative hits reported in the `XA` tag of a record
+    /// (bwa mem -h). If a read has more hits than the cap, the `XA` tag is omitted
+    /// entirely. `alt` is the cap used when any hit is to an ALT contig. The defaults
+    /// are 5 and 200.
+    /// Panics in debug builds if `primary` or `alt` is negative.
+    pub fn set_max_xa_hits(mut self, primary: i32, alt: i32) -> BwaSettings {
+        debug_check(non_negative("max XA hits", primary));
+        debug_check(non_negative("max XA hits for ALT contigs", alt));
+        self.bwa_settings.max_XA_hits = primary;
+        self.bwa_settings.max_XA_hits_alt = alt;
+        self
     }
-}
 
-fn add_ref_to_bam_header(header: &mut Header, seq_name: &str, seq_len: usize) {
-    let mut header_rec = HeaderRecord::new(b"SQ");
-    header_rec.push_tag(b"SN", &seq_name);
-    header_rec.push_tag(b"LN", &seq_len);
-    header.push_record(&header_rec);
-}
+    /// Set the minimum score of an alternative hit reported in the `XA` tag, as a fraction
+    /// of the primary alignment's score. The default is 0.8; `bwa mem` has no option for it.
+    /// Panics in debug builds if `xa_drop_ratio` isn't between 0 and 1.
+    pub fn set_xa_drop_ratio(mut self, xa_drop_ratio: f32) -> BwaSettings {
+        debug_check(fraction("XA drop ratio", xa_drop_ratio));
+        self.bwa_settings.XA_drop_ratio = xa_drop_ratio;
+        self
+    }
 
-/// Paired-end statistics structure used by BWA to score paired-end reads
-pub struct PairedEndStats {
-    inner: [bwa_sys::mem_pestat_t; 4],
-}
+    /// Set occurrence threshold for re-seeding: a long seed is only split
+    /// if it occurs fewer than `split_width` times in the reference.
+    /// Panics in debug builds if `split_width` isn't positive.
+    pub fn set_split_width(mut self, split_width: i32) -> BwaSettings {
+        debug_check(positive("split width", split_width));
+        self.bwa_settings.split_width = split_width;
+        self
+    }
 
-impl PairedEndStats {
-    /// Generate a 'simple' paired-end read structure that standard forward-reverse
-    /// pairs as created by TruSeq, Nextera, or Chromium Genome sample preparations.
-    pub fn simple(avg: f64, std: f64, low: i32, high: i32) -> PairedEndStats {
-        let pe_stat_null = || bwa_sys::mem_pestat_t {
-            failed: 1,
-            low: 0,
-            high: 0,
-            avg: 0.0,
-            std: 100.0,
-        };
+    /// Set the maximum number of occurrences of a seed in the reference: seeds that occur
+    /// more often are only sampled at `max_occ` of their locations (bwa mem -c). Raising it
+    /// finds more alignments in repetitive regions, at a speed cost. The default is 500.
+    /// Panics in debug builds if `max_occ` isn't positive.
+    pub fn set_max_occ(mut self, max_occ: i32) -> BwaSettings {
+        debug_check(positive("max occurrences", max_occ));
+        self.bwa_settings.max_occ = max_occ;
+        self
+    }
 
-        let pes = [
-            pe_stat_null(),
-            bwa_sys::mem_pestat_t {
-                failed: 0,
-                low,
-                high,
-                avg,
-                std,
-            },
-            pe_stat_null(),
-            pe_stat_null(),
-        ];
+    /// Set the largest gap between two seeds in a chain (bwa mem -G). Seeds further apart
+    /// start a new chain. The default is 10,000.
+    /// Panics in debug builds if `max_chain_gap` isn't positive.
+    pub fn set_max_chain_gap(mut self, max_chain_gap: i32) -> BwaSettings {
+        debug_check(positive("max chain gap", max_chain_gap));
+        self.bwa_settings.max_chain_gap = max_chain_gap;
+        self
+    }
 
-        PairedEndStats { inner: pes }
+    /// Set the minimum number of read bases covered by the seeds of a chain (bwa mem -W).
+    /// Chains with less seed coverage are dropped before extension. The default is 0.
+    /// Panics in debug builds if `min_chain_weight` is negative.
+    pub fn set_min_chain_weight(mut self, min_chain_weight: i32) -> BwaSettings {
+        debug_check(non_negative("min chain weight", min_chain_weight));
+        self.bwa_settings.min_chain_weight = min_chain_weight;
+        self
     }
 
-    pub fn default() -> PairedEndStats {
-        Self::simple(200.0, 100.0, 35, 600)
+    /// Set the maximum number of chains extended for each read. `bwa mem` has no option
+    /// for it and extends every chain by default.
+    /// Panics in debug builds if `max_chain_extend` isn't positive.
+    pub fn set_max_chain_extend(mut self, max_chain_extend: i32) -> BwaSettings {
+        debug_check(positive("max chain extend", max_chain_extend));
+        self.bwa_settings.max_chain_extend = max_chain_extend;
+        self
     }
-}
 
-/// A BWA aligner. Carries everything required to align
-/// reads to a reference and generate BAM records.
-pub struct BwaAligner {
-    reference: BwaReference,
-    header_view: Arc<Mutex<HeaderView>>,
-    settings: BwaSettings,
-    pe_stats: PairedEndStats,
-}
-// this is not automatically derived because of an interior
-//   mutable pointer inside HeaderView. It _is_ mutated
-//   by the Record::from_sam function, so guard it with a mutex
-unsafe impl Sync for BwaAligner {}
+    /// Set the maximum number of rounds of mate rescue for each read of a pair (bwa mem -m).
+    /// 0 disables mate rescue, as `set_skip_mate_rescue` does. The default is 50.
+    /// Panics in debug builds if `max_mate_sw` is negative.
+    pub fn set_max_mate_sw(mut self, max_mate_sw: i32) -> BwaSettings {
+        debug_check(non_negative("max mate rescue rounds", max_mate_sw));
+        self.bwa_settings.max_matesw = max_mate_sw;
+        self
+    }
 
-impl BwaAligner {
-    /// Load a BWA reference from the given path and use default BWA settings and paired-end structure.
-    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<BwaAligner, ReferenceError> {
-        let bwa_ref = BwaReference::open(path)?;
-        Ok(BwaAligner::new(
-            bwa_ref,
-            BwaSettings::new(),
-            PairedEndStats::default(),
-        ))
+    /// Set the number of worker threads used to align a batch of reads with
+    /// `BwaAligner::align_read_pairs` (bwa mem -t). Each batch call starts its own
+    /// worker threads, so it is safe to align batches from several Rust threads at
+    /// once against a shared reference -- the reference index is only read during
+    /// alignment -- but the total thread count is then multiplied accordingly.
+    /// Panics in debug builds if `n` isn't positive.
+    pub fn set_num_threads(mut self, n: i32) -> BwaSettings {
+        debug_check(positive("number of threads", n));
+        self.bwa_settings.n_threads = n;
+        self
     }
 
-    pub fn new(
-        reference: BwaReference,
-        settings: BwaSettings,
-        pe_stats: PairedEndStats,
-    ) -> BwaAligner {
-        let header = reference.create_bam_header();
-        let header_view = Arc::new(Mutex::new(HeaderView::from_header(&header)));
+    /// Set the number of bases each thread processes in a batch of reads read from FASTQ
+    /// files by `BwaAligner::align_fastq_pair_to_bam`. As with `bwa mem`, each batch holds
+    /// `chunk_size * threads` bases. The default is 10,000,000.
+    /// Panics in debug builds if `chunk_size` isn't positive.
+    pub fn set_chunk_size(mut self, chunk_size: i32) -> BwaSettings {
+        debug_check(positive("chunk size", chunk_size));
+        self.bwa_settings.chunk_size = chunk_size;
+        self
+    }
 
-        BwaAligner {
-            reference,
-            header_view,
-            settings,
-            pe_stats,
-        }
+    /// Use soft clipping rather than hard clipping for supplementary alignments,
+    /// so every record carries the full read sequence (bwa mem -Y)
+    pub fn set_softclip_supplementary(mut self) -> BwaSettings {
+        self.bwa_settings.flag |= bwa_sys::MEM_F_SOFTCLIP as i32;
+        self
     }
 
-    /// Align a read-pair to the reference.
-    pub fn align_read_pair(
-        &self,
-        name: &[u8],
-        r1: &[u8],
-        q1: &[u8],
-        r2: &[u8],
-        q2: &[u8],
-    ) -> (Vec<Record>, Vec<Record>) {
-        let name = CString::new(name).unwrap();
-        let raw_name = name.into_raw();
+    /// Output all found alignments for single-end or unpaired paired-end reads,
+    /// as secondary records (bwa mem -a)
+    pub fn set_output_all(mut self) -> BwaSettings {
+        self.bwa_settings.flag |= bwa_sys::MEM_F_ALL as i32;
+        self
+    }
 
-        // Prep input data -- need to make copy of reads since BWA will edit the strings in-place
-        // FIXME - set an id -- used for a random hash
-        let mut r1 = Vec::from(r1);
-        let mut q1 = Vec::from(q1);
-        let mut r2 = Vec::from(r2);
-        let mut q2 = Vec::from(q2);
+    /// Don't rescue unmapped or poorly-mapped mates by Smith-Waterman alignment
+    /// near the other read of the pair (bwa mem -S)
+    pub fn set_skip_mate_rescue(mut self) -> BwaSettings {
+        self.bwa_settings.flag |= bwa_sys::MEM_F_NO_RESCUE as i32;
+        self
+    }
 
-        let read1 = bwa_sys::bseq1_t {
-            l_seq: r1.len() as i32,
-            name: raw_name,
-            seq: r1.as_mut_ptr() as *mut i8,
-            qual: q1.as_mut_ptr() as *mut i8,
-            comment: ptr::null_mut(),
-            id: 0,
-            sam: ptr::null_mut(),
-        };
+    /// Treat each read 1 followed by its read 2 in an interleaved input as a pair (bwa mem -p).
+    /// This is implied by `BwaAligner::align_interleaved`, and recorded in `command_line`.
+    pub fn set_smart_pairing(mut self) -> BwaSettings {
+        self.bwa_settings.flag |= bwa_sys::MEM_F_SMARTPE as i32;
+        self
+    }
 
-        let read2 = bwa_sys::bseq1_t {
-            l_seq: r2.len() as i32,
-            name: raw_name,
-            seq: r2.as_mut_ptr() as *mut i8,
-            qual: q2.as_mut_ptr() as *mut i8,
-            comment: ptr::null_mut(),
-            id: 0,
-            sam: ptr::null_mut(),
-        };
+    /// Don't use the pairing to choose the best alignment of each read, so each mate
+    /// reports its own best alignment and pairs are never marked as proper (bwa mem -P)
+    pub fn set_skip_pairing(mut self) -> BwaSettings {
+        self.bwa_settings.flag |= bwa_sys::MEM_F_NOPAIRING as i32;
+        self
+    }
 
-        let mut reads = [read1, read2];
+    /// The `bwa mem` command line equivalent to these settings, for use in `@PG` header lines
+    pub fn command_line(&self) -> String {
+        let s = &self.bwa_settings;
+        let mut cl = format!(
+            "bwa mem -t {} -k {} -w {} -d {} -r {} -D {} -y {} -c {} -A {} -B {} -O {},{} -E {},{} -L {},{} -U {} -T {} -h {},{} -G {} -m {}",
+            s.n_threads,
+            s.min_seed_len,
+            s.w,
+            s.zdrop,
+            s.split_factor,
+            s.drop_ratio,
+            s.max_mem_intv,
+            s.max_occ,
+            s.a,
+            s.b,
+            s.o_del,
+            s.o_ins,
+            s.e_del,
+            s.e_ins,
+            s.pen_clip5,
+            s.pen_clip3,
+            s.pen_unpaired,
+            s.T,
+            s.max_XA_hits,
+            s.max_XA_hits_alt,
+            s.max_chain_gap,
+            s.max_matesw
+        );
 
-        // Align the read pair. BWA will write the SAM data back to the bwa_sys::bseq1_t.sam field
-        unsafe {
-            let r = *(self.reference.bwt_data);
-            let settings = self.settings.bwa_settings;
-            bwa_sys::mem_process_seq_pe(
-                &settings,
-                r.bwt,
-                r.bns,
-                r.pac,
-                reads.as_mut_ptr(),
-                self.pe_stats.inner.as_ptr(),
-            );
-            let _ = CString::from_raw(raw_name);
+        if s.min_chain_weight != 0 {
+            cl.push_str(&format!(" -W {}", s.min_chain_weight));
         }
-
-        // Parse the results from the SAM output & convert the htslib Records
-        let sam1 = unsafe { CStr::from_ptr(reads[0].sam) };
-        let sam2 = unsafe { CStr::from_ptr(reads[1].sam) };
-
-        let recs1 = self.parse_sam_to_records(sam1.to_bytes());
-        let recs2 = self.parse_sam_to_records(sam2.to_bytes());
-
-        unsafe {
-            libc::free(reads[0].sam as *mut libc::c_void);
-            libc::free(reads[1].sam as *mut libc::c_void);
+        if s.flag & bwa_sys::MEM_F_ALL as i32 != 0 {
+            cl.push_str(" -a");
+        }
+        if s.flag & bwa_sys::MEM_F_SOFTCLIP as i32 != 0 {
+            cl.push_str(" -Y");
+        }
+        if s.flag & bwa_sys::MEM_F_NO_MULTI as i32 != 0 {
+            cl.push_str(" -M");
+        }
+        if s.flag & bwa_sys::MEM_F_NO_RESCUE as i32 != 0 {
+            cl.push_str(" -S");
+        }
+        if s.flag & bwa_sys::MEM_F_NOPAIRING as i32 != 0 {
+            cl.push_str(" -P");
+        }
+        if s.flag & bwa_sys::MEM_F_SMARTPE as i32 != 0 {
+            cl.push_str(" -p");
+        }
+        if self.append_comment {
+            cl.push_str(" -C");
         }
 
-        (recs1, recs2)
+        cl
     }
 
-    fn parse_sam_to_records(&self, sam: &[u8]) -> Vec<Record> {
-        let mut records = Vec::new();
+    /// Mark shorter splits as secondary (bwa mem -M). The shorter parts of a chimeric
+    /// alignment are then flagged as secondary (0x100) rather than supplementary (0x800),
+    /// for compatibility with Picard and older versions of GATK.
+    pub fn set_no_multi(mut self) -> BwaSettings {
+        self.bwa_settings.flag |= bwa_sys::MEM_F_NO_MULTI as i32;
+        self
+    }
 
-        for slc in sam.split(|x| *x == b'\n') {
-            if slc.len() > 0 {
-                let record = {
-                    let header_view = self.header_view.lock().unwrap();
-                    Record::from_sam(&header_view, slc).unwrap()
-                };
-                records.push(record);
-            }
-        }
+    /// Append the FASTQ comment of each read to its records (bwa mem -C), when aligning
+    /// with `BwaAligner::align_fastq` and the other FASTQ methods. As with `bwa mem`, the
+    /// comments must be tab-separated SAM tags such as `BX:Z:ACGT-1`, or the records will
+    /// fail to parse.
+    pub fn set_append_comment(mut self) -> BwaSettings {
+        self.append_comment = true;
+        self
+    }
+}
 
-        records
+impl std::fmt::Debug for BwaSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("BwaSettings").field(&self.params()).finish()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[cfg(feature = "serde")]
+impl serde::Serialize for BwaSettings {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.params().serialize(serializer)
+    }
+}
 
-    fn load_aligner() -> BwaAligner {
-        let aln = BwaAligner::from_path("tests/test_ref.fa");
-        aln.unwrap()
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BwaSettings {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let params = BwaParams::deserialize(deserializer)?;
+        Ok(BwaSettings::from_params(&params))
     }
+}
 
-    #[test]
-    fn test_load_aligner() {
-        let _ = load_aligner();
+/// Collects BWA parameters and validates them when building a `BwaSettings`, rather
+/// than passing nonsensical values to BWA. Flags such as `set_no_multi` can be set on
+/// the built settings, as they need no validation.
+pub struct BwaSettingsBuilder {
+    settings: BwaSettings,
+}
+
+impl Default for BwaSettingsBuilder {
+    fn default() -> BwaSettingsBuilder {
+        BwaSettingsBuilder::new()
+    }
+}
+
+impl BwaSettingsBuilder {
+    /// Start from the default BWA parameters
+    pub fn new() -> BwaSettingsBuilder {
+        BwaSettingsBuilder {
+            settings: BwaSettings::new(),
+        }
+    }
+
+    /// Alignment scores, as for `BwaSettings::set_scores`
+    pub fn scores(
+        mut self,
+        matchp: i32,
+        mismatch: i32,
+        gap_open: i32,
+        gap_extend: i32,
+    ) -> BwaSettingsBuilder {
+        let s = &mut self.settings.bwa_settings;
+        s.a = matchp;
+        s.b = mismatch;
+        s.o_del = gap_open;
+        s.o_ins = gap_open;
+        s.e_del = gap_extend;
+        s.e_ins = gap_extend;
+        self
+    }
+
+    /// Separate deletion and insertion gap penalties, as for `BwaSettings::set_gap_penalties`
+    pub fn gap_penalties(
+        mut self,
+        o_del: i32,
+        e_del: i32,
+        o_ins: i32,
+        e_ins: i32,
+    ) -> BwaSettingsBuilder {
+        let s = &mut self.settings.bwa_settings;
+        s.o_del = o_del;
+        s.e_del = e_del;
+        s.o_ins = o_ins;
+        s.e_ins = e_ins;
+        self
+    }
+
+    /// Clipping penalties (bwa mem -L)
+    pub fn clip_scores(mut self, clip5: i32, clip3: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.pen_clip5 = clip5;
+        self.settings.bwa_settings.pen_clip3 = clip3;
+        self
+    }
+
+    /// Unpaired read penalty (bwa mem -U)
+    pub fn unpaired(mut self, unpaired: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.pen_unpaired = unpaired;
+        self
+    }
+
+    /// Minimum alignment score for a record to be output (bwa mem -T)
+    pub fn min_output_score(mut self, min_score: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.T = min_score;
+        self
+    }
+
+    /// Band width for banded alignment (bwa mem -w)
+    pub fn band_width(mut self, band_width: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.w = band_width;
+        self
+    }
+
+    /// Off-diagonal X-dropoff for alignment extension (bwa mem -d)
+    pub fn zdrop(mut self, zdrop: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.zdrop = zdrop;
+        self
+    }
+
+    /// Minimum seed length (bwa mem -k)
+    pub fn min_seed_len(mut self, min_seed_len: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.min_seed_len = min_seed_len;
+        self
+    }
+
+    /// Re-seeding trigger (bwa mem -r)
+    pub fn split_factor(mut self, split_factor: f32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.split_factor = split_factor;
+        self
+    }
+
+    /// Chain drop ratio (bwa mem -D)
+    pub fn drop_ratio(mut self, drop_ratio: f32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.drop_ratio = drop_ratio;
+        self
+    }
+
+    /// Caps on the number of alternative hits in the `XA` tag (bwa mem -h)
+    pub fn max_xa_hits(mut self, primary: i32, alt: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.max_XA_hits = primary;
+        self.settings.bwa_settings.max_XA_hits_alt = alt;
+        self
+    }
+
+    /// Minimum score of an alternative hit in the `XA` tag, relative to the primary
+    pub fn xa_drop_ratio(mut self, xa_drop_ratio: f32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.XA_drop_ratio = xa_drop_ratio;
+        self
+    }
+
+    /// Occurrence threshold for re-seeding (bwa mem -y)
+    pub fn split_width(mut self, split_width: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.split_width = split_width;
+        self
+    }
+
+    /// Maximum seed occurrences (bwa mem -c)
+    pub fn max_occ(mut self, max_occ: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.max_occ = max_occ;
+        self
+    }
+
+    /// Largest gap between seeds in a chain (bwa mem -G)
+    pub fn max_chain_gap(mut self, max_chain_gap: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.max_chain_gap = max_chain_gap;
+        self
+    }
+
+    /// Minimum seed coverage of a chain (bwa mem -W)
+    pub fn min_chain_weight(mut self, min_chain_weight: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.min_chain_weight = min_chain_weight;
+        self
+    }
+
+    /// Maximum number of chains extended for each read
+    pub fn max_chain_extend(mut self, max_chain_extend: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.max_chain_extend = max_chain_extend;
+        self
+    }
+
+    /// Maximum rounds of mate rescue (bwa mem -m)
+    pub fn max_mate_sw(mut self, max_mate_sw: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.max_matesw = max_mate_sw;
+        self
+    }
+
+    /// Number of BWA worker threads for batch alignment (bwa mem -t)
+    pub fn num_threads(mut self, n: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.n_threads = n;
+        self
+    }
+
+    /// Bases per thread in each batch of FASTQ reads
+    pub fn chunk_size(mut self, chunk_size: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.chunk_size = chunk_size;
+        self
+    }
+
+    /// Largest insert size considered when inferring paired-end stats
+    pub fn max_insert(mut self, max_insert: i32) -> BwaSettingsBuilder {
+        self.settings.bwa_settings.max_ins = max_insert;
+        self
+    }
+
+    /// Validate the parameters and build the settings
+    pub fn build(mut self) -> Result<BwaSettings, SettingsError> {
+        {
+            let s = &self.settings.bwa_settings;
+            positive("match score", s.a)?;
+            non_negative("mismatch penalty", s.b)?;
+            non_negative("gap open penalty", s.o_del)?;
+            positive("gap extension penalty", s.e_del)?;
+            non_negative("insertion open penalty", s.o_ins)?;
+            positive("insertion extension penalty", s.e_ins)?;
+            non_negative("5' clipping penalty", s.pen_clip5)?;
+            non_negative("3' clipping penalty", s.pen_clip3)?;
+            non_negative("unpaired penalty", s.pen_unpaired)?;
+            non_negative("minimum output score", s.T)?;
+            positive("band width", s.w)?;
+            positive("zdrop", s.zdrop)?;
+            positive("minimum seed length", s.min_seed_len)?;
+            positive("split factor", s.split_factor)?;
+            fraction("drop ratio", s.drop_ratio)?;
+            non_negative("max XA hits", s.max_XA_hits)?;
+            non_negative("max XA hits for ALT contigs", s.max_XA_hits_alt)?;
+            fraction("XA drop ratio", s.XA_drop_ratio)?;
+            positive("split width", s.split_width)?;
+            positive("max occurrences", s.max_occ)?;
+            positive("max chain gap", s.max_chain_gap)?;
+            non_negative("min chain weight", s.min_chain_weight)?;
+            positive("max chain extend", s.max_chain_extend)?;
+            non_negative("max mate rescue rounds", s.max_matesw)?;
+            positive("number of threads", s.n_threads)?;
+            positive("chunk size", s.chunk_size)?;
+            positive("max insert size", s.max_ins)?;
+        }
+
+        let s = &mut self.settings.bwa_settings;
+        unsafe {
+            bwa_sys::bwa_fill_scmat(s.a, s.b, s.mat.as_mut_ptr());
+        }
+        Ok(self.settings)
+    }
+}
+
+/// The scoring matrix BWA derives from a match score and mismatch penalty
+fn score_matrix(a: i32, b: i32) -> [i8; 25] {
+    let mut mat = [0; 25];
+    unsafe {
+        bwa_sys::bwa_fill_scmat(a, b, mat.as_mut_ptr());
+    }
+    mat
+}
+
+fn positive<T>(param: &'static str, value: T) -> Result<(), SettingsError>
+where
+    T: PartialOrd + Default + std::fmt::Display,
+{
+    require(value > T::default(), param, "positive", value)
+}
+
+fn non_negative(param: &'static str, value: i32) -> Result<(), SettingsError> {
+    require(value >= 0, param, "non-negative", value)
+}
+
+fn fraction(param: &'static str, value: f32) -> Result<(), SettingsError> {
+    require(
+        (0.0..=1.0).contains(&value),
+        param,
+        "between 0 and 1",
+        value,
+    )
+}
+
+fn require<T: std::fmt::Display>(
+    ok: bool,
+    param: &'static str,
+    requirement: &'static str,
+    value: T,
+) -> Result<(), SettingsError> {
+    if ok {
+        Ok(())
+    } else {
+        Err(SettingsError {
+            param,
+            value: value.to_string(),
+            requirement,
+        })
+    }
+}
+
+/// The `BwaSettings` setters can't return an error without breaking chaining, so they
+/// panic on invalid values in debug builds. `BwaSettingsBuilder` returns the error instead.
+fn debug_check(res: Result<(), SettingsError>) {
+    if cfg!(debug_assertions) {
+        if let Err(e) = res {
+            panic!("invalid BWA setting: {}", e);
+        }
+    }
+}
+
+/// A BWA parameter outside its valid range
+#[derive(Debug, thiserror::Error)]
+#[error("{param} must be {requirement}, got {value}")]
+pub struct SettingsError {
+    /// The offending parameter, e.g. "band width"
+    pub param: &'static str,
+    /// The rejected value
+    pub value: String,
+    requirement: &'static str,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct ReferenceError(String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct ReadGroupError(String);
+
+/// Errors encountered while aligning reads
+#[derive(Debug, thiserror::Error)]
+pub enum AlignError {
+    #[error("read name contains a nul byte: {0}")]
+    InvalidName(#[from] NulError),
+    #[cfg(feature = "htslib")]
+    #[error("couldn't parse BWA output as SAM: {line}")]
+    SamParse {
+        line: String,
+        #[source]
+        source: rust_htslib::errors::Error,
+    },
+    #[error("read has {seq_len} bases but {qual_len} qualities")]
+    MismatchedLengths { seq_len: usize, qual_len: usize },
+    #[error("quality {qual} is below the quality offset {offset}")]
+    InvalidQual { qual: u8, offset: u8 },
+    #[error("quality offset {0} is below 33")]
+    InvalidQualOffset(u8),
+    #[error("read of length {0} is too long for BWA")]
+    ReadTooLong(usize),
+    #[cfg(feature = "htslib")]
+    #[error("couldn't write BAM: {0}")]
+    BamWrite(#[source] rust_htslib::errors::Error),
+    #[error("BWA produced no SAM output for read")]
+    NoOutput,
+    #[error("interleaved batch has an odd number of reads: {0}")]
+    OddInterleavedBatch(usize),
+    #[error("read comment contains a nul byte")]
+    InvalidComment,
+    #[error("couldn't add {tag} tag to record: {msg}")]
+    InvalidTag { tag: String, msg: String },
+    #[error("couldn't parse BWA output as SAM: {0}")]
+    MalformedSam(String),
+}
+
+/// Any error returned by this crate, for callers that want to handle loading the
+/// reference and aligning reads with a single error type
+#[derive(Debug, thiserror::Error)]
+pub enum BwaError {
+    #[error("{0}")]
+    Settings(#[from] SettingsError),
+    #[error("{0}")]
+    Reference(#[from] ReferenceError),
+    #[error("{0}")]
+    ReadGroup(#[from] ReadGroupError),
+    #[error("{0}")]
+    Align(#[from] AlignError),
+    #[cfg(feature = "htslib")]
+    #[error("{0}")]
+    Tag(#[from] record::TagParseError),
+}
+
+/// Check that a read can be passed to BWA. An empty quality slice
+/// is allowed, and means the read has no base qualities.
+fn check_lengths(seq: &[u8], qual: &[u8]) -> Result<(), AlignError> {
+    if seq.len() > i32::MAX as usize {
+        return Err(AlignError::ReadTooLong(seq.len()));
+    }
+    if !qual.is_empty() && seq.len() != qual.len() {
+        return Err(AlignError::MismatchedLengths {
+            seq_len: seq.len(),
+            qual_len: qual.len(),
+        });
+    }
+    Ok(())
+}
+
+/// The id of a read for BWA's pseudo-random tie-breaking: a 64-bit FNV-1a hash of its
+/// name, folded to a non-negative `i32`
+fn read_id(name: &[u8]) -> i32 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in name {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    ((hash ^ (hash >> 32)) & 0x7fff_ffff) as i32
+}
+
+/// Copy the SAM output that BWA wrote to a `bseq1_t` into `buf`, freeing BWA's copy
+fn take_sam_into(read: &mut bwa_sys::bseq1_t, buf: &mut Vec<u8>) -> Result<(), AlignError> {
+    buf.clear();
+    if read.sam.is_null() {
+        return Err(AlignError::NoOutput);
+    }
+
+    buf.extend_from_slice(unsafe { CStr::from_ptr(read.sam) }.to_bytes());
+    unsafe {
+        libc::free(read.sam as *mut libc::c_void);
+    }
+    read.sam = ptr::null_mut();
+    Ok(())
+}
+
+/// Copy `name` into `buf` as a nul-terminated string for BWA
+fn fill_name(name: &[u8], buf: &mut Vec<u8>) -> Result<(), AlignError> {
+    if name.contains(&0) {
+        // only allocates on error, for the `NulError`
+        CString::new(name)?;
+    }
+    buf.clear();
+    buf.extend_from_slice(name);
+    buf.push(0);
+    Ok(())
+}
+
+/// Copy a read comment into `buf` as a C string, returning the pointer to pass to BWA,
+/// or null if there's no comment
+fn fill_comment(comment: Option<&[u8]>, buf: &mut Vec<u8>) -> Result<*mut i8, AlignError> {
+    match comment {
+        Some(comment) => {
+            if comment.contains(&0) {
+                return Err(AlignError::InvalidComment);
+            }
+            buf.clear();
+            buf.extend_from_slice(comment);
+            buf.push(0);
+            Ok(buf.as_mut_ptr() as *mut i8)
+        }
+        None => Ok(ptr::null_mut()),
+    }
+}
+
+#[cfg(feature = "htslib")]
+fn push_pg_record(header: &mut Header, id: &str, program: &str, cl: &str) {
+    let mut pg = HeaderRecord::new(b"PG");
+    pg.push_tag(b"ID", id);
+    pg.push_tag(b"PN", program);
+    pg.push_tag(b"VN", version());
+    pg.push_tag(b"CL", cl);
+    header.push_record(&pg);
+}
+
+/// Group the records of consecutive reads into pairs
+#[cfg(feature = "htslib")]
+fn into_pairs(recs: Vec<Vec<Record>>) -> Vec<PairRecords> {
+    let mut recs = recs.into_iter();
+    let mut pairs = Vec::with_capacity(recs.len() / 2);
+    while let (Some(recs1), Some(recs2)) = (recs.next(), recs.next()) {
+        pairs.push((recs1, recs2));
+    }
+    pairs
+}
+
+/// Quality pointer for a `bseq1_t` -- BWA writes `*` qualities if it's null
+fn qual_ptr(qual: &mut Vec<u8>) -> *mut i8 {
+    if qual.is_empty() {
+        ptr::null_mut()
+    } else {
+        qual.as_mut_ptr() as *mut i8
+    }
+}
+
+/// A read group, written to the BAM header as an `@RG` line and
+/// tagged onto aligned records with an `RG` tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadGroup {
+    id: String,
+    tags: Vec<(String, String)>,
+}
+
+impl ReadGroup {
+    /// Create a read group with the given ID
+    pub fn new(id: &str) -> ReadGroup {
+        ReadGroup {
+            id: id.to_string(),
+            tags: vec![("ID".to_string(), id.to_string())],
+        }
+    }
+
+    /// Set the sample name (`SM`)
+    pub fn sample(self, sample: &str) -> ReadGroup {
+        self.tag("SM", sample)
+    }
+
+    /// Set the library (`LB`)
+    pub fn library(self, library: &str) -> ReadGroup {
+        self.tag("LB", library)
+    }
+
+    /// Set the sequencing platform (`PL`), e.g. `ILLUMINA`
+    pub fn platform(self, platform: &str) -> ReadGroup {
+        self.tag("PL", platform)
+    }
+
+    fn tag(mut self, tag: &str, value: &str) -> ReadGroup {
+        match self.tags.iter_mut().find(|t| t.0 == tag) {
+            Some(t) => t.1 = value.to_string(),
+            None => self.tags.push((tag.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    /// The read group ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Parse a read group header line such as `@RG\tID:foo\tSM:bar`. As with `bwa mem -R`,
+    /// fields may be separated by an escaped `\t` rather than a literal tab.
+    pub fn parse(rg_line: &str) -> Result<ReadGroup, ReadGroupError> {
+        let rg_line = rg_line.replace("\\t", "\t");
+        let mut fields = rg_line.trim_end().split('\t');
+
+        if fields.next() != Some("@RG") {
+            return Err(ReadGroupError(format!(
+                "read group line must start with @RG: {:?}",
+                rg_line
+            )));
+        }
+
+        let mut id = None;
+        let mut tags = Vec::new();
+        for field in fields {
+            if field.len() < 3 || field.as_bytes()[2] != b':' {
+                return Err(ReadGroupError(format!(
+                    "malformed read group field {:?} in {:?}",
+                    field, rg_line
+                )));
+            }
+
+            let (tag, value) = (&field[..2], &field[3..]);
+            if tag == "ID" {
+                id = Some(value.to_string());
+            }
+            tags.push((tag.to_string(), value.to_string()));
+        }
+
+        match id {
+            Some(id) => Ok(ReadGroup { id, tags }),
+            None => Err(ReadGroupError(format!(
+                "read group line has no ID field: {:?}",
+                rg_line
+            ))),
+        }
+    }
+}
+
+/// The BWA index files a reference was loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexInfo {
+    /// Prefix of the index files, e.g. `ref.fa` for `ref.fa.bwt`
+    pub prefix: PathBuf,
+    /// Whether the index files are named `<prefix>.64.bwt` etc., as written by
+    /// `bwa index -6` and for large references. The prefix includes the `.64`.
+    pub is_64: bool,
+}
+
+/// Algorithm used to construct the BWT when building a BWA index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexAlgorithm {
+    /// Let BWA choose: BWT-SW for references longer than 50Mbp, IS otherwise
+    Auto,
+    /// BWT-SW algorithm, suitable for large genomes
+    BwtSw,
+    /// IS linear-time algorithm, suitable for references up to a few Gbp
+    Is,
+}
+
+impl IndexAlgorithm {
+    fn as_bwtalgo(self) -> i32 {
+        match self {
+            IndexAlgorithm::Auto => bwa_sys::BWTALGO_AUTO as i32,
+            IndexAlgorithm::BwtSw => bwa_sys::BWTALGO_BWTSW as i32,
+            IndexAlgorithm::Is => bwa_sys::BWTALGO_IS as i32,
+        }
+    }
+}
+
+bitflags! {
+    /// Selects which components of a BWA index to load from disk.
+    pub struct IdxLoadFlags: i32 {
+        /// The BWT and suffix array (`.bwt`, `.sa`)
+        const BWT = bwa_sys::BWA_IDX_BWT as i32;
+        /// The contig names and lengths (`.ann`, `.amb`)
+        const BNS = bwa_sys::BWA_IDX_BNS as i32;
+        /// The packed reference sequence (`.pac`)
+        const PAC = bwa_sys::BWA_IDX_PAC as i32;
+        /// Everything required for alignment
+        const ALL = bwa_sys::BWA_IDX_ALL as i32;
+    }
+}
+
+/// A BWA reference object to perform alignments to.
+/// Must be loaded from a BWA index created with `bwa index`
+pub struct BwaReference {
+    bwt_data: *const bwa_sys::bwaidx_t,
+    loaded: IdxLoadFlags,
+    contig_names: Vec<String>,
+    contig_lengths: Vec<usize>,
+    contig_tids: HashMap<Vec<u8>, i32>,
+    index_info: IndexInfo,
+    /// The address and length of the index image mapped by `open_mmap`
+    mapping: Option<(*mut libc::c_void, usize)>,
+}
+// the index is never mutated after loading, so it can be shared and moved between threads
+unsafe impl Sync for BwaReference {}
+unsafe impl Send for BwaReference {}
+
+impl BwaReference {
+    /// Load a BWA reference from disk. Pass the fasta filename of the
+    /// original reference as `path`. Returns an error if the contigs of the index
+    /// don't match the fasta's `.fai`, e.g. if the index was built from another fasta.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<BwaReference, ReferenceError> {
+        Self::open_with_flags(path, IdxLoadFlags::ALL)
+    }
+
+    /// Load only the selected components of a BWA reference from disk. The contig
+    /// metadata (`IdxLoadFlags::BNS`) is always required, but the BWT and packed
+    /// sequence can be skipped if the reference is only used to generate BAM headers.
+    /// A reference that is missing components can't be used for alignment.
+    pub fn open_with_flags<P: AsRef<Path>>(
+        path: P,
+        flags: IdxLoadFlags,
+    ) -> Result<BwaReference, ReferenceError> {
+        if !flags.contains(IdxLoadFlags::BNS) {
+            return Err(ReferenceError(format!(
+                "BNS must be loaded to open reference: {:?}",
+                path.as_ref()
+            )));
+        }
+
+        let info = find_index_prefix(path.as_ref(), flags)?;
+        let idx_file = path_cstring(&info.prefix)?;
+        let idx = unsafe { bwa_sys::bwa_idx_load(idx_file.as_ptr(), flags.bits()) };
+
+        if idx.is_null() {
+            return Err(ReferenceError(format!(
+                "couldn't load reference: {:?}",
+                path.as_ref()
+            )));
+        }
+
+        let reference = Self::from_idx(idx, flags, info);
+        check_fasta(
+            path.as_ref(),
+            &reference.contig_names,
+            &reference.contig_lengths,
+        )?;
+        Ok(reference)
+    }
+
+    /// Load a BWA reference from POSIX shared memory, as staged by `bwa shm`. If the
+    /// index isn't in shared memory yet, it's loaded from disk and staged first, so
+    /// later processes can load it without reading and copying the index files. Pass the
+    /// fasta filename of the original reference as `path`.
+    ///
+    /// The index stays in shared memory after the process exits, until it's removed
+    /// with `bwa shm -d`. Indexes are identified by the file name of their prefix, and
+    /// shouldn't be staged by several processes at once.
+    pub fn open_shm<P: AsRef<Path>>(path: P) -> Result<BwaReference, ReferenceError> {
+        let info = find_index_prefix(path.as_ref(), IdxLoadFlags::ALL)?;
+        let hint = path_cstring(&info.prefix)?;
+
+        let idx = unsafe {
+            if bwa_sys::bwa_shm_test(hint.as_ptr()) == 0 {
+                let disk_idx = bwa_sys::bwa_idx_load(hint.as_ptr(), IdxLoadFlags::ALL.bits());
+                if disk_idx.is_null() {
+                    return Err(ReferenceError(format!(
+                        "couldn't load reference: {:?}",
+                        path.as_ref()
+                    )));
+                }
+                let ret = bwa_sys::bwa_shm_stage(disk_idx, hint.as_ptr(), ptr::null());
+                bwa_sys::bwa_idx_destroy(disk_idx);
+                if ret != 0 {
+                    return Err(ReferenceError(format!(
+                        "couldn't stage reference in shared memory: {:?}",
+                        path.as_ref()
+                    )));
+                }
+            }
+            bwa_sys::bwa_idx_load_from_shm(hint.as_ptr())
+        };
+
+        if idx.is_null() {
+            return Err(ReferenceError(format!(
+                "couldn't load reference from shared memory: {:?}",
+                path.as_ref()
+            )));
+        }
+
+        // bwa_idx_destroy only frees the process-local parts of a shared memory index
+        let reference = Self::from_idx(idx, IdxLoadFlags::ALL, info);
+        check_fasta(
+            path.as_ref(),
+            &reference.contig_names,
+            &reference.contig_lengths,
+        )?;
+        Ok(reference)
+    }
+
+    /// Write an image of the index for the reference at `path` to `image`, to be loaded with
+    /// `open_mmap`. Pass the fasta filename of the original reference as `path`. The whole
+    /// index is loaded to write the image, which is about as large as the `.bwt`, `.sa`,
+    /// `.pac`, `.ann` and `.amb` combined.
+    ///
+    /// The image is the flat layout staged by `bwa shm`, after a header holding a magic
+    /// number and layout version. It's only readable on the platform that wrote it. The image
+    /// is written to a temporary file next to `image` and renamed into place once complete,
+    /// so a partial image is never mapped.
+    pub fn write_image<P: AsRef<Path>, Q: AsRef<Path>>(
+        path: P,
+        image: Q,
+    ) -> Result<(), ReferenceError> {
+        let info = find_index_prefix(path.as_ref(), IdxLoadFlags::ALL)?;
+        let hint = path_cstring(&info.prefix)?;
+        let idx = unsafe { bwa_sys::bwa_idx_load(hint.as_ptr(), IdxLoadFlags::ALL.bits()) };
+        if idx.is_null() {
+            return Err(ReferenceError(format!(
+                "couldn't load reference: {:?}",
+                path.as_ref()
+            )));
+        }
+
+        let image = image.as_ref();
+        let tmp = with_ext(image, &format!("{}.tmp", std::process::id()));
+        let res = unsafe {
+            // moves the parts of the index into a single buffer, freed by bwa_idx_destroy
+            bwa_sys::bwa_idx2mem(idx);
+            let mem = std::slice::from_raw_parts((*idx).mem, (*idx).l_mem as usize);
+            write_image_file(&tmp, mem).and_then(|_| std::fs::rename(&tmp, image))
+        };
+        unsafe {
+            bwa_sys::bwa_idx_destroy(idx);
+        }
+
+        res.map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            ReferenceError(format!("couldn't write index image {:?}: {}", image, e))
+        })
+    }
+
+    /// Load a BWA reference by memory-mapping an index image written by `write_image`, so
+    /// the BWT, suffix array and packed sequence are read in place from the page cache
+    /// rather than copied into each process. Processes mapping the same image share one copy
+    /// of the index. Pass the fasta filename of the original reference as `path`: the index
+    /// files must still exist, but aren't loaded.
+    ///
+    /// Returns an error if the image wasn't written by `write_image` with the current layout
+    /// version, or if its length doesn't match the index it describes. The image stays
+    /// mapped until the reference is dropped. It mustn't be modified in place while mapped,
+    /// but it can be replaced by renaming a new image over it, as `write_image` does.
+    pub fn open_mmap<P: AsRef<Path>, Q: AsRef<Path>>(
+        path: P,
+        image: Q,
+    ) -> Result<BwaReference, ReferenceError> {
+        let info = find_index_prefix(path.as_ref(), IdxLoadFlags::BNS)?;
+        let image = image.as_ref();
+        let image_error =
+            |e: String| ReferenceError(format!("couldn't map index image {:?}: {}", image, e));
+
+        let mut file = File::open(image).map_err(|e| image_error(e.to_string()))?;
+        let len = file
+            .metadata()
+            .map_err(|e| image_error(e.to_string()))?
+            .len() as usize;
+        let mut header = [0; IMAGE_HEADER_LEN];
+        file.read_exact(&mut header)
+            .map_err(|_| image_error("not an index image".to_string()))?;
+        let l_mem = check_image_header(&header, len).map_err(image_error)?;
+
+        let mem = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if mem == libc::MAP_FAILED {
+            return Err(image_error(std::io::Error::last_os_error().to_string()));
+        }
+
+        // bwa_mem2idx aborts if the index it reads isn't exactly `l_mem` long, so check the
+        // lengths recorded in the index before handing it over
+        let index = unsafe { (mem as *mut u8).add(IMAGE_HEADER_LEN) };
+        let read_len = flat_index_len(unsafe { std::slice::from_raw_parts(index, l_mem) });
+        if read_len != Some(l_mem) {
+            unsafe {
+                libc::munmap(mem, len);
+            }
+            return Err(image_error(
+                "image doesn't match the index it describes".to_string(),
+            ));
+        }
+
+        // BWA only reads from the index, so it can point into the read-only mapping. As for
+        // a shared memory index, bwa_idx_destroy then frees only the process-local parts,
+        // and the mapping is unmapped on drop.
+        let idx = unsafe {
+            libc::calloc(1, std::mem::size_of::<bwa_sys::bwaidx_t>()) as *mut bwa_sys::bwaidx_t
+        };
+        if idx.is_null() {
+            unsafe {
+                libc::munmap(mem, len);
+            }
+            return Err(image_error("out of memory".to_string()));
+        }
+        unsafe {
+            bwa_sys::bwa_mem2idx(l_mem as i64, index, idx);
+            (*idx).is_shm = 1;
+        }
+
+        let mut reference = Self::from_idx(idx, IdxLoadFlags::ALL, info);
+        reference.mapping = Some((mem, len));
+        check_fasta(
+            path.as_ref(),
+            &reference.contig_names,
+            &reference.contig_lengths,
+        )?;
+        Ok(reference)
+    }
+
+    fn from_idx(
+        idx: *mut bwa_sys::bwaidx_t,
+        flags: IdxLoadFlags,
+        index_info: IndexInfo,
+    ) -> BwaReference {
+        let mut contig_names = Vec::new();
+        let mut contig_lengths = Vec::new();
+        let num_contigs = unsafe { (*(*idx).bns).n_seqs };
+
+        for i in 0..num_contigs as isize {
+            unsafe {
+                let name = CStr::from_ptr((*(*(*idx).bns).anns.offset(i)).name);
+                let sz = (*(*(*idx).bns).anns.offset(i)).len;
+
+                let name_string = name.to_owned().into_string().unwrap();
+                contig_names.push(name_string);
+                contig_lengths.push(sz as usize)
+            }
+        }
+
+        let contig_tids = contig_names
+            .iter()
+            .enumerate()
+            .map(|(tid, name)| (name.clone().into_bytes(), tid as i32))
+            .collect();
+
+        BwaReference {
+            bwt_data: idx,
+            loaded: flags,
+            contig_names,
+            contig_lengths,
+            contig_tids,
+            index_info,
+            mapping: None,
+        }
+    }
+
+    /// Build a BWA index for the reference fasta at `fasta`, equivalent to running
+    /// `bwa index -a <algo> <fasta>`. The index files are written next to the fasta,
+    /// so the reference can be loaded afterwards with `BwaReference::open(fasta)`.
+    pub fn build_index<P: AsRef<Path>>(
+        fasta: P,
+        algo: IndexAlgorithm,
+    ) -> Result<(), ReferenceError> {
+        Self::index(fasta, None, algo)
+    }
+
+    /// Build a BWA index for the reference fasta at `fasta`, equivalent to running
+    /// `bwa index -a <algo> -p <prefix> <fasta>`. The index files are written to
+    /// `<prefix>.bwt`, `<prefix>.pac` etc, or next to the fasta if `prefix` is `None`.
+    /// The reference can be loaded afterwards with `BwaReference::open(prefix)`.
+    pub fn index<P: AsRef<Path>>(
+        fasta: P,
+        prefix: Option<&Path>,
+        algo: IndexAlgorithm,
+    ) -> Result<(), ReferenceError> {
+        let fasta = fasta.as_ref();
+        // BWA exits the process if it can't read the fasta, so check it up-front
+        if let Err(e) = File::open(fasta) {
+            return Err(ReferenceError(format!(
+                "couldn't read reference fasta {:?}: {}",
+                fasta, e
+            )));
+        }
+
+        // Like `bwa index`, use the fasta path as the prefix by default
+        let prefix = prefix.unwrap_or(fasta);
+        let fa = path_cstring(fasta)?;
+        let prefix = path_cstring(prefix)?;
+        let block_size = 10_000_000;
+        let ret = unsafe {
+            bwa_sys::bwa_idx_build(fa.as_ptr(), prefix.as_ptr(), algo.as_bwtalgo(), block_size)
+        };
+
+        if ret != 0 {
+            return Err(ReferenceError(format!(
+                "couldn't build index for reference: {:?}",
+                fasta
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Build and load an index of the given `(name, sequence)` contigs, for small
+    /// references such as amplicon panels. The index is built in a temporary directory,
+    /// which is removed once the index is loaded into memory. The contigs are numbered
+    /// in the given order.
+    pub fn from_sequences(seqs: &[(&str, &[u8])]) -> Result<BwaReference, ReferenceError> {
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+        if seqs.is_empty() {
+            return Err(ReferenceError(
+                "no sequences given for reference".to_string(),
+            ));
+        }
+
+        let mut fasta = Vec::new();
+        for &(name, seq) in seqs {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(ReferenceError(format!("invalid contig name: {:?}", name)));
+            }
+            if seq.is_empty() {
+                return Err(ReferenceError(format!("contig {} is empty", name)));
+            }
+            fasta.push(b'>');
+            fasta.extend_from_slice(name.as_bytes());
+            fasta.push(b'\n');
+            fasta.extend_from_slice(seq);
+            fasta.push(b'\n');
+        }
+
+        let dir = std::env::temp_dir().join(format!(
+            "rust-bwa-ref-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::SeqCst)
+        ));
+        let fasta_path = dir.join("ref.fa");
+        let res = std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(&fasta_path, &fasta))
+            .map_err(|e| ReferenceError(format!("couldn't write reference to {:?}: {}", dir, e)))
+            .and_then(|_| Self::build_index(&fasta_path, IndexAlgorithm::Is))
+            .and_then(|_| Self::open(&fasta_path));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        res
+    }
+
+    /// The index components that were loaded for this reference
+    pub fn loaded(&self) -> IdxLoadFlags {
+        self.loaded
+    }
+
+    /// The index files the reference was loaded from. For a reference built with
+    /// `from_sequences`, these files have since been removed.
+    pub fn index_info(&self) -> &IndexInfo {
+        &self.index_info
+    }
+
+    /// Names of the reference contigs, indexed by tid
+    pub fn contig_names(&self) -> &[String] {
+        &self.contig_names
+    }
+
+    /// Lengths of the reference contigs, indexed by tid
+    pub fn contig_lengths(&self) -> &[usize] {
+        &self.contig_lengths
+    }
+
+    /// Number of contigs in the reference
+    pub fn n_contigs(&self) -> usize {
+        self.contig_names.len()
+    }
+
+    /// Length of the named contig, or `None` if it isn't in the reference. The lookup is a
+    /// single hash map access, so it's cheap enough for per-interval use, e.g. clipping
+    /// BED regions.
+    pub fn contig_length(&self, name: &str) -> Option<usize> {
+        self.contig_tid(name)
+            .map(|tid| self.contig_lengths[tid as usize])
+    }
+
+    /// The tid of the named contig, matching the order of the `@SQ` lines in
+    /// `create_bam_header` and so the tids of aligned records. Contigs are looked up
+    /// in a map built when the reference is loaded.
+    pub fn contig_tid(&self, name: &str) -> Option<i32> {
+        self.contig_tids.get(name.as_bytes()).cloned()
+    }
+
+    #[deprecated(note = "renamed to `contig_length`")]
+    pub fn contig_len(&self, name: &str) -> Option<usize> {
+        self.contig_length(name)
+    }
+
+    #[deprecated(note = "renamed to `contig_tid`")]
+    pub fn tid(&self, name: &str) -> Option<i32> {
+        self.contig_tid(name)
+    }
+
+    /// Name of the contig with the given tid, or `None` if the tid is out of range
+    pub fn tid_to_name(&self, tid: i32) -> Option<&str> {
+        if tid < 0 {
+            return None;
+        }
+        self.contig_names.get(tid as usize).map(|n| n.as_str())
+    }
+
+    /// Whether contig `tid` is an ALT contig, as listed in the `<prefix>.alt` file next to
+    /// the index, e.g. for GRCh38. BWA reads the `.alt` file when the index is loaded, and
+    /// aligns with the same ALT handling as `bwa mem`: hits to ALT contigs don't reduce the
+    /// mapping quality of hits to the primary assembly, and are reported as secondary
+    /// alignments or in the XA tag. Returns false for an out of range tid.
+    pub fn is_alt_contig(&self, tid: i32) -> bool {
+        if self.tid_to_name(tid).is_none() {
+            return false;
+        }
+        unsafe { (*(*(*self.bwt_data).bns).anns.offset(tid as isize)).is_alt != 0 }
+    }
+
+    /// Fetch the bases of contig `tid` from `start` to `end`, 0-based and half-open, from
+    /// the packed reference sequence held in memory, as uppercase ACGTN. BWA packs
+    /// ambiguous bases as random bases, so they're restored as N from the positions in
+    /// the `.amb` file. Requires the `.pac` to have been loaded, see `IdxLoadFlags::PAC`.
+    pub fn fetch(&self, tid: i32, start: u64, end: u64) -> Result<Vec<u8>, ReferenceError> {
+        if !self.loaded.contains(IdxLoadFlags::PAC) {
+            return Err(ReferenceError(format!(
+                "PAC must be loaded to fetch reference sequence, loaded {:?}",
+                self.loaded
+            )));
+        }
+        let name = self
+            .tid_to_name(tid)
+            .ok_or_else(|| ReferenceError(format!("no contig with tid {}", tid)))?;
+        let len = self.contig_lengths[tid as usize] as u64;
+        if start > end || end > len {
+            return Err(ReferenceError(format!(
+                "can't fetch {}:{}-{}, {} has length {}",
+                name, start, end, name, len
+            )));
+        }
+        if start == end {
+            return Ok(Vec::new());
+        }
+
+        let idx = unsafe { &*self.bwt_data };
+        let bns = unsafe { &*idx.bns };
+        let offset = unsafe { (*bns.anns.offset(tid as isize)).offset };
+        let (beg, end) = (offset + start as i64, offset + end as i64);
+
+        // the range is within the forward strand of one contig, so bns_get_seq never has
+        // to reverse-complement across the end of the forward strand
+        let mut seq = unsafe {
+            let mut n = 0;
+            let codes = bwa_sys::bns_get_seq(bns.l_pac, idx.pac, beg, end, &mut n);
+            let seq = std::slice::from_raw_parts(codes, n as usize)
+                .iter()
+                .map(|&c| b"ACGTN"[c as usize])
+                .collect::<Vec<u8>>();
+            libc::free(codes as *mut libc::c_void);
+            seq
+        };
+
+        if bns.n_holes > 0 {
+            let holes = unsafe { std::slice::from_raw_parts(bns.ambs, bns.n_holes as usize) };
+            for hole in holes {
+                let hole_beg = hole.offset.max(beg);
+                let hole_end = (hole.offset + hole.len as i64).min(end);
+                for pos in hole_beg..hole_end {
+                    seq[(pos - beg) as usize] = b'N';
+                }
+            }
+        }
+
+        Ok(seq)
+    }
+
+    /// Find the supermaximal exact matches (SMEMs) of at least `min_len` bases between `seq`
+    /// and the reference, as `bwa fastmap` does. These are the seeds BWA starts alignments
+    /// from. Matches don't span ambiguous bases. Requires the BWT to have been loaded, see
+    /// `IdxLoadFlags::BWT`.
+    pub fn find_smems(&self, seq: &[u8], min_len: i32) -> Result<Vec<Smem>, ReferenceError> {
+        if !self.loaded.contains(IdxLoadFlags::BWT) {
+            return Err(ReferenceError(format!(
+                "BWT must be loaded to find SMEMs, loaded {:?}",
+                self.loaded
+            )));
+        }
+
+        // the 2-bit encoding of bwa's nst_nt4_table, with 4 for any ambiguous base
+        let query: Vec<u8> = seq
+            .iter()
+            .map(|base| match base.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => 4,
+            })
+            .collect();
+
+        let mut smems = Vec::new();
+        unsafe {
+            let itr = bwa_sys::smem_itr_init((*self.bwt_data).bwt);
+            bwa_sys::smem_set_query(itr, query.len() as i32, query.as_ptr());
+            loop {
+                let matches = bwa_sys::smem_next(itr);
+                if matches.is_null() {
+                    break;
+                }
+                for i in 0..(*matches).n as isize {
+                    let intv = *(*matches).a.offset(i);
+                    let smem = Smem {
+                        query_start: (intv.info >> 32) as usize,
+                        query_end: intv.info as u32 as usize,
+                        occurrences: intv.x[2],
+                        sa_begin: intv.x[0],
+                    };
+                    if (smem.query_end - smem.query_start) as i64 >= min_len as i64 {
+                        smems.push(smem);
+                    }
+                }
+            }
+            bwa_sys::smem_itr_destroy(itr);
+        }
+
+        Ok(smems)
+    }
+
+    /// The reference positions of up to `max_hits` occurrences of an SMEM found by
+    /// `find_smems`, looked up in the suffix array as `bwa fastmap -w` does. A match on
+    /// the reverse strand is reported at its leftmost position on the forward strand.
+    /// Occurrences that span two contigs are skipped. Requires the BWT and suffix array
+    /// to have been loaded, see `IdxLoadFlags::BWT`.
+    pub fn smem_hits(&self, smem: &Smem, max_hits: usize) -> Result<Vec<SmemHit>, ReferenceError> {
+        if !self.loaded.contains(IdxLoadFlags::BWT) {
+            return Err(ReferenceError(format!(
+                "BWT must be loaded to locate SMEMs, loaded {:?}",
+                self.loaded
+            )));
+        }
+
+        // an SMEM found in another reference may lie outside this suffix array
+        let seq_len = unsafe { (*(*self.bwt_data).bwt).seq_len };
+        let in_bounds = smem
+            .sa_begin
+            .checked_add(smem.occurrences)
+            .is_some_and(|end| end <= seq_len);
+        if smem.query_end <= smem.query_start || !in_bounds {
+            return Err(ReferenceError(format!(
+                "SMEM wasn't found in this reference: {:?}",
+                smem
+            )));
+        }
+
+        let len = (smem.query_end - smem.query_start) as i64;
+        let n_hits = smem.occurrences.min(max_hits as u64);
+        let mut hits = Vec::with_capacity(n_hits as usize);
+        unsafe {
+            let idx = &*self.bwt_data;
+            let l_pac = (*idx.bns).l_pac;
+            for k in 0..n_hits {
+                // the suffix array covers the forward strand followed by the reverse
+                // complement, as in bns_depos
+                let sa = bwa_sys::bwt_sa(idx.bwt, smem.sa_begin + k) as i64;
+                let is_reverse = sa >= l_pac;
+                let pos = if is_reverse {
+                    (l_pac << 1) - sa - len
+                } else {
+                    sa
+                };
+
+                let tid = bwa_sys::bns_pos2rid(idx.bns, pos);
+                if tid < 0 || bwa_sys::bns_pos2rid(idx.bns, pos + len - 1) != tid {
+                    continue;
+                }
+                let offset = (*(*idx.bns).anns.offset(tid as isize)).offset;
+                hits.push(SmemHit {
+                    tid,
+                    pos: pos - offset,
+                    is_reverse,
+                });
+            }
+        }
+
+        Ok(hits)
+    }
+
+    #[cfg(feature = "htslib")]
+    pub fn create_bam_header(&self) -> Header {
+        let mut header = Header::new();
+        self.populate_bam_header(&mut header);
+        header
+    }
+
+    #[cfg(feature = "htslib")]
+    pub fn populate_bam_header(&self, header: &mut Header) {
+        for (contig_name, &len) in self.contig_names.iter().zip(self.contig_lengths.iter()) {
+            add_ref_to_bam_header(header, contig_name, len);
+        }
+    }
+}
+
+impl Drop for BwaReference {
+    fn drop(&mut self) {
+        unsafe {
+            bwa_sys::bwa_idx_destroy(self.bwt_data as *mut bwa_sys::bwaidx_t);
+            if let Some((mem, len)) = self.mapping {
+                libc::munmap(mem, len);
+            }
+        }
+    }
+}
+
+/// Magic number at the start of an index image written by `BwaReference::write_image`
+const IMAGE_MAGIC: [u8; 8] = *b"BWAIMAGE";
+
+/// Version of the index image layout: the header, followed by the flat index written by
+/// `bwa_idx2mem`. Bump it if either changes.
+const IMAGE_VERSION: u32 = 1;
+
+/// Length of the index image header: the magic number, the layout version, 4 bytes of
+/// padding and the length of the flat index, in native byte order. A multiple of 8, so the
+/// flat index is aligned for BWA.
+const IMAGE_HEADER_LEN: usize = 24;
+
+/// Write the flat index `mem` to `path` as an index image, after its header
+fn write_image_file(path: &Path, mem: &[u8]) -> std::io::Result<()> {
+    let mut header = [0; IMAGE_HEADER_LEN];
+    header[..8].copy_from_slice(&IMAGE_MAGIC);
+    header[8..12].copy_from_slice(&IMAGE_VERSION.to_ne_bytes());
+    header[16..].copy_from_slice(&(mem.len() as u64).to_ne_bytes());
+
+    let mut file = File::create(path)?;
+    file.write_all(&header)?;
+    file.write_all(mem)?;
+    file.sync_all()
+}
+
+/// Check the header of an index image of `len` bytes, returning the length of its flat index
+fn check_image_header(header: &[u8; IMAGE_HEADER_LEN], len: usize) -> Result<usize, String> {
+    if header[..8] != IMAGE_MAGIC {
+        return Err("not an index image".to_string());
+    }
+
+    let mut version = [0; 4];
+    version.copy_from_slice(&header[8..12]);
+    let version = u32::from_ne_bytes(version);
+    if version != IMAGE_VERSION {
+        return Err(format!(
+            "image layout version {} isn't supported, expected {}",
+            version, IMAGE_VERSION
+        ));
+    }
+
+    let mut l_mem = [0; 8];
+    l_mem.copy_from_slice(&header[16..]);
+    let l_mem = u64::from_ne_bytes(l_mem);
+    if l_mem != (len - IMAGE_HEADER_LEN) as u64 {
+        return Err(format!(
+            "image holds {} bytes of index, but its header records {}",
+            len - IMAGE_HEADER_LEN,
+            l_mem
+        ));
+    }
+    Ok(l_mem as usize)
+}
+
+/// The number of bytes `bwa_mem2idx` reads from the flat index `mem`, following the layout
+/// written by `bwa_idx2mem`, or `None` if the index it describes runs past the end of `mem`
+fn flat_index_len(mem: &[u8]) -> Option<usize> {
+    // the struct of type `T` at `offset` in `mem`, and the offset just after it
+    fn read<T: Copy>(mem: &[u8], offset: usize) -> Option<(T, usize)> {
+        let end = offset.checked_add(std::mem::size_of::<T>())?;
+        if end > mem.len() {
+            return None;
+        }
+        let value = unsafe { ptr::read_unaligned(mem[offset..].as_ptr() as *const T) };
+        Some((value, end))
+    }
+
+    // skip past `n` items of `size` bytes at `offset`
+    let skip = |offset: usize, n: u64, size: usize| -> Option<usize> {
+        usize::try_from(n)
+            .ok()?
+            .checked_mul(size)?
+            .checked_add(offset)
+    };
+
+    let (bwt, k) = read::<bwa_sys::bwt_t>(mem, 0)?;
+    let k = skip(k, bwt.bwt_size, 4)?;
+    let k = skip(k, bwt.n_sa, std::mem::size_of::<bwa_sys::bwtint_t>())?;
+
+    let (bns, k) = read::<bwa_sys::bntseq_t>(mem, k)?;
+    let n_holes = u64::try_from(bns.n_holes).ok()?;
+    let n_seqs = u64::try_from(bns.n_seqs).ok()?;
+    let mut k = skip(k, n_holes, std::mem::size_of::<bwa_sys::bntamb1_t>())?;
+    k = skip(k, n_seqs, std::mem::size_of::<bwa_sys::bntann1_t>())?;
+
+    // the name and annotation of each contig, as nul-terminated strings
+    for _ in 0..n_seqs * 2 {
+        let len = mem.get(k..)?.iter().position(|&b| b == 0)?;
+        k += len + 1;
+    }
+
+    skip(k, u64::try_from(bns.l_pac).ok()? / 4 + 1, 1)
+}
+
+/// The index file with extension `ext` for `prefix`, e.g. `ref.fa.bwt` for `ref.fa`
+fn with_ext(prefix: &Path, ext: &str) -> PathBuf {
+    let mut p = prefix.as_os_str().to_owned();
+    p.push(".");
+    p.push(ext);
+    PathBuf::from(p)
+}
+
+/// Convert a path to pass to BWA, which can't handle paths containing nul bytes
+fn path_cstring(path: &Path) -> Result<CString, ReferenceError> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| ReferenceError(format!("path contains a nul byte: {:?}", path)))
+}
+
+/// Find the prefix of the BWA index files for `path`, which may be the indexed fasta or
+/// the index prefix itself, checking that all the files required by `flags` exist.
+/// Indexes of large references may be named `<prefix>.64.bwt` etc., and are preferred
+/// over a standard index with the same prefix, as in `bwa_idx_infer_prefix`.
+fn find_index_prefix(path: &Path, flags: IdxLoadFlags) -> Result<IndexInfo, ReferenceError> {
+    // the .bwt is always required by bwa_idx_load to locate the index, and the
+    // contig metadata requires the .pac as well as the .ann and .amb
+    let mut exts = vec!["bwt"];
+    if flags.contains(IdxLoadFlags::BWT) {
+        exts.push("sa");
+    }
+    exts.extend(&["ann", "amb", "pac"]);
+
+    // the index may have been built with a prefix that omits the fasta extension
+    let mut candidates = vec![path.to_path_buf()];
+    let path_str = path.to_string_lossy();
+    for fasta_ext in &[".fa", ".fasta", ".fna", ".fa.gz", ".fasta.gz", ".fna.gz"] {
+        if let Some(prefix) = path_str.strip_suffix(fasta_ext) {
+            candidates.push(PathBuf::from(prefix));
+        }
+    }
+
+    for candidate in &candidates {
+        for &(ref prefix, is_64) in &[
+            (with_ext(candidate, "64"), true),
+            (candidate.clone(), false),
+        ] {
+            if !with_ext(prefix, "bwt").is_file() {
+                continue;
+            }
+
+            let missing: Vec<_> = exts
+                .iter()
+                .map(|ext| with_ext(prefix, ext))
+                .filter(|f| !f.is_file())
+                .collect();
+
+            if missing.is_empty() {
+                return Ok(IndexInfo {
+                    prefix: prefix.clone(),
+                    is_64,
+                });
+            }
+            return Err(missing_index_error(path, &missing));
+        }
+    }
+
+    let missing: Vec<_> = exts.iter().map(|ext| with_ext(path, ext)).collect();
+    Err(missing_index_error(path, &missing))
+}
+
+/// Check that the contigs of a loaded index match the reference fasta at `path`, to
+/// catch an index that was built from a different fasta. The contig names and lengths
+/// are compared with the fasta's `.fai` when there is one. Otherwise an uncompressed
+/// fasta is only checked to be large enough to hold the indexed bases, as scanning the
+/// whole fasta would take as long as loading the index. Nothing is checked if `path` is
+/// an index prefix rather than the fasta.
+fn check_fasta(path: &Path, names: &[String], lengths: &[usize]) -> Result<(), ReferenceError> {
+    let fasta_len = match std::fs::metadata(path) {
+        Ok(ref meta) if meta.is_file() => meta.len(),
+        _ => return Ok(()),
+    };
+
+    let mismatch = |msg: String| {
+        ReferenceError(format!(
+            "BWA index doesn't match reference fasta {:?}: {}. Run `bwa index` on the reference fasta to rebuild it",
+            path, msg
+        ))
+    };
+
+    let mut fai = path.as_os_str().to_owned();
+    fai.push(".fai");
+    let fai = PathBuf::from(fai);
+
+    if fai.is_file() {
+        let contents = std::fs::read_to_string(&fai)
+            .map_err(|e| ReferenceError(format!("couldn't read {:?}: {}", fai, e)))?;
+        let contigs: Vec<(&str, Option<usize>)> = contents
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let mut fields = line.split('\t');
+                let name = fields.next().unwrap_or("");
+                (name, fields.next().and_then(|len| len.parse().ok()))
+            })
+            .collect();
+
+        if contigs.len() != names.len() {
+            return Err(mismatch(format!(
+                "the index has {} contigs but {:?} has {}",
+                names.len(),
+                fai,
+                contigs.len()
+            )));
+        }
+        for (tid, (&(name, len), (idx_name, &idx_len))) in
+            contigs.iter().zip(names.iter().zip(lengths)).enumerate()
+        {
+            if name != idx_name || len != Some(idx_len) {
+                return Err(mismatch(format!(
+                    "contig {} is {} ({} bp) in the index but {} ({}) in {:?}",
+                    tid,
+                    idx_name,
+                    idx_len,
+                    name,
+                    len.map_or("invalid length".to_string(), |len| format!("{} bp", len)),
+                    fai
+                )));
+            }
+        }
+    } else if !path.to_string_lossy().ends_with(".gz") {
+        let indexed: usize = lengths.iter().sum();
+        if fasta_len < indexed as u64 {
+            return Err(mismatch(format!(
+                "the fasta is {} bytes, but the index has {} bases",
+                fasta_len, indexed
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn missing_index_error(path: &Path, missing: &[PathBuf]) -> ReferenceError {
+    let missing: Vec<_> = missing.iter().map(|f| f.display().to_string()).collect();
+    ReferenceError(format!(
+        "BWA index files missing for reference {:?}: {}. Run `bwa index` on the reference fasta to create them",
+        path,
+        missing.join(", ")
+    ))
+}
+
+#[cfg(feature = "htslib")]
+fn add_ref_to_bam_header(header: &mut Header, seq_name: &str, seq_len: usize) {
+    let mut header_rec = HeaderRecord::new(b"SQ");
+    header_rec.push_tag(b"SN", seq_name);
+    header_rec.push_tag(b"LN", seq_len);
+    header.push_record(&header_rec);
+}
+
+/// Orientation of a read-pair, as distinguished by BWA's paired-end model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairOrientation {
+    FF = 0,
+    FR = 1,
+    RF = 2,
+    RR = 3,
+}
+
+/// Insert size distribution of the read-pairs in one orientation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InsertSizeStats {
+    pub avg: f64,
+    pub std: f64,
+    /// Smallest insert size considered for proper pairs
+    pub low: i32,
+    /// Largest insert size considered for proper pairs
+    pub high: i32,
+}
+
+/// Paired-end statistics structure used by BWA to score paired-end reads
+pub struct PairedEndStats {
+    inner: [bwa_sys::mem_pestat_t; 4],
+}
+
+fn pe_stat_null() -> bwa_sys::mem_pestat_t {
+    bwa_sys::mem_pestat_t {
+        failed: 1,
+        low: 0,
+        high: 0,
+        avg: 0.0,
+        std: 100.0,
+    }
+}
+
+impl PairedEndStats {
+    /// Generate a 'simple' paired-end read structure that standard forward-reverse
+    /// pairs as created by TruSeq, Nextera, or Chromium Genome sample preparations.
+    pub fn simple(avg: f64, std: f64, low: i32, high: i32) -> PairedEndStats {
+        let pes = [
+            pe_stat_null(),
+            bwa_sys::mem_pestat_t {
+                failed: 0,
+                low,
+                high,
+                avg,
+                std,
+            },
+            pe_stat_null(),
+            pe_stat_null(),
+        ];
+
+        PairedEndStats { inner: pes }
+    }
+
+    pub fn default() -> PairedEndStats {
+        Self::simple(200.0, 100.0, 35, 600)
+    }
+
+    /// Infer the insert size distribution of each pair orientation from a sample of
+    /// read-pairs, as `bwa mem` does for each batch of reads. Orientations
+    /// with too few confidently-mapped pairs are marked as failed, and aren't used to
+    /// score pairs.
+    pub fn infer(aligner: &BwaAligner, pairs: &[ReadPair]) -> PairedEndStats {
+        let mut regs = Vec::with_capacity(pairs.len() * 2);
+        for pair in pairs {
+            regs.push(aligner.find_regions(&pair.r1));
+            regs.push(aligner.find_regions(&pair.r2));
+        }
+
+        let mut pes = [pe_stat_null(); 4];
+        unsafe {
+            let l_pac = (*(*aligner.reference.bwt_data).bns).l_pac;
+            bwa_sys::mem_pestat(
+                &aligner.settings.bwa_settings,
+                l_pac,
+                regs.len() as i32,
+                regs.as_ptr(),
+                pes.as_mut_ptr(),
+            );
+
+            for reg in &regs {
+                libc::free(reg.a as *mut libc::c_void);
+            }
+        }
+
+        PairedEndStats { inner: pes }
+    }
+
+    /// The insert size distribution of pairs in the given orientation,
+    /// or `None` if that orientation isn't used to score pairs
+    pub fn insert_size(&self, orientation: PairOrientation) -> Option<InsertSizeStats> {
+        let pes = &self.inner[orientation as usize];
+        if pes.failed != 0 {
+            return None;
+        }
+
+        Some(InsertSizeStats {
+            avg: pes.avg,
+            std: pes.std,
+            low: pes.low,
+            high: pes.high,
+        })
+    }
+}
+
+/// Counts of the read-pairs aligned by `BwaAligner::align_fastq_pair_to_bam`
+#[cfg(feature = "htslib")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AlignStats {
+    /// Number of read-pairs aligned
+    pub pairs: u64,
+    /// Number of read-pairs with at least one mapped read
+    pub mapped: u64,
+    /// Number of read-pairs aligned as a proper pair
+    pub properly_paired: u64,
+}
+
+#[cfg(feature = "htslib")]
+impl AlignStats {
+    fn add_pair(&mut self, recs1: &[Record], recs2: &[Record]) {
+        fn primary(recs: &[Record]) -> Option<&Record> {
+            recs.iter()
+                .find(|r| !r.is_secondary() && !r.is_supplementary())
+        }
+
+        self.pairs += 1;
+        if let (Some(r1), Some(r2)) = (primary(recs1), primary(recs2)) {
+            if !r1.is_unmapped() || !r2.is_unmapped() {
+                self.mapped += 1;
+            }
+            if r1.is_proper_pair() {
+                self.properly_paired += 1;
+            }
+        }
+    }
+}
+
+/// Flagstat-like counts of the reads aligned by `BwaAligner::align_read_pairs_with_metrics`
+#[cfg(feature = "htslib")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignmentMetrics {
+    /// Number of reads aligned, not counting secondary or supplementary records
+    pub reads: u64,
+    /// Number of reads whose primary record is mapped
+    pub mapped: u64,
+    /// Number of reads aligned as part of a proper pair
+    pub properly_paired: u64,
+    /// Number of secondary records
+    pub secondary: u64,
+    /// Number of supplementary records
+    pub supplementary: u64,
+    /// Number of duplicate records -- always 0, as BWA doesn't mark duplicates
+    pub duplicates: u64,
+    /// Number of mapped reads with each mapping quality, indexed by mapping quality
+    pub mapq_histogram: Vec<u64>,
+    /// Number of proper pairs with each insert size, indexed by the absolute TLEN of read 1.
+    /// Larger inserts are counted in the last bin, at the maximum insert size.
+    pub insert_size_histogram: Vec<u64>,
+}
+
+#[cfg(feature = "htslib")]
+impl AlignmentMetrics {
+    /// Empty metrics, with an insert size histogram up to `max_insert_size`
+    pub fn new(max_insert_size: usize) -> AlignmentMetrics {
+        AlignmentMetrics {
+            reads: 0,
+            mapped: 0,
+            properly_paired: 0,
+            secondary: 0,
+            supplementary: 0,
+            duplicates: 0,
+            mapq_histogram: vec![0; 256],
+            insert_size_histogram: vec![0; max_insert_size + 1],
+        }
+    }
+
+    /// Count the records of an aligned read-pair
+    pub fn add_pair(&mut self, recs1: &[Record], recs2: &[Record]) {
+        for rec in recs1.iter().chain(recs2) {
+            if rec.is_secondary() {
+                self.secondary += 1;
+            } else if rec.is_supplementary() {
+                self.supplementary += 1;
+            } else {
+                self.add_primary(rec);
+            }
+        }
+    }
+
+    fn add_primary(&mut self, rec: &Record) {
+        self.reads += 1;
+        if rec.is_duplicate() {
+            self.duplicates += 1;
+        }
+        if rec.is_unmapped() {
+            return;
+        }
+
+        self.mapped += 1;
+        self.mapq_histogram[rec.mapq() as usize] += 1;
+        if rec.is_proper_pair() {
+            self.properly_paired += 1;
+            if rec.is_first_in_template() {
+                let max_bin = self.insert_size_histogram.len() - 1;
+                let insert_size = (rec.insert_size().unsigned_abs() as usize).min(max_bin);
+                self.insert_size_histogram[insert_size] += 1;
+            }
+        }
+    }
+}
+
+/// Number of read-pairs aligned by each Rayon task in `BwaAligner::align_pairs_par`
+#[cfg(all(feature = "htslib", feature = "rayon"))]
+const PAR_CHUNK_PAIRS: usize = 256;
+
+/// A BWA aligner. Carries everything required to align
+/// reads to a reference and generate BAM records.
+///
+/// Aligning doesn't mutate the aligner, so a single aligner can be shared between any
+/// number of threads, e.g. in an `Arc`, without them contending on a lock. There's no
+/// need for a pool of aligners. Aligners with different settings can share one loaded
+/// reference with `BwaAligner::with_reference`.
+pub struct BwaAligner {
+    reference: Arc<BwaReference>,
+    read_group: Option<ReadGroup>,
+    qual_offset: u8,
+    settings: BwaSettings,
+    pe_stats: PairedEndStats,
+}
+
+impl BwaAligner {
+    /// Load a BWA reference from the given path and use default BWA settings and paired-end structure.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<BwaAligner, ReferenceError> {
+        let bwa_ref = BwaReference::open(path)?;
+        Ok(BwaAligner::new(
+            bwa_ref,
+            BwaSettings::new(),
+            PairedEndStats::default(),
+        ))
+    }
+
+    /// Create an aligner. Panics if `reference` wasn't loaded with all of `IdxLoadFlags::ALL`.
+    pub fn new(
+        reference: BwaReference,
+        settings: BwaSettings,
+        pe_stats: PairedEndStats,
+    ) -> BwaAligner {
+        match BwaAligner::try_new(reference, settings, pe_stats) {
+            Ok(aligner) => aligner,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Create an aligner, returning an error if `reference` wasn't loaded with
+    /// all of `IdxLoadFlags::ALL`.
+    pub fn try_new(
+        reference: BwaReference,
+        settings: BwaSettings,
+        pe_stats: PairedEndStats,
+    ) -> Result<BwaAligner, ReferenceError> {
+        BwaAligner::try_with_reference(Arc::new(reference), settings, pe_stats)
+    }
+
+    /// Create an aligner using a reference that may be shared with other aligners, so
+    /// that aligners with different settings don't each need their own copy of the index.
+    /// Panics if `reference` wasn't loaded with all of `IdxLoadFlags::ALL`.
+    pub fn with_reference(
+        reference: Arc<BwaReference>,
+        settings: BwaSettings,
+        pe_stats: PairedEndStats,
+    ) -> BwaAligner {
+        match BwaAligner::try_with_reference(reference, settings, pe_stats) {
+            Ok(aligner) => aligner,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Create an aligner using a shared reference, returning an error if `reference`
+    /// wasn't loaded with all of `IdxLoadFlags::ALL`.
+    pub fn try_with_reference(
+        reference: Arc<BwaReference>,
+        settings: BwaSettings,
+        pe_stats: PairedEndStats,
+    ) -> Result<BwaAligner, ReferenceError> {
+        if !reference.loaded.contains(IdxLoadFlags::ALL) {
+            return Err(ReferenceError(format!(
+                "reference index is incomplete, loaded {:?} but alignment requires {:?}",
+                reference.loaded,
+                IdxLoadFlags::ALL
+            )));
+        }
+
+        Ok(BwaAligner {
+            reference,
+            read_group: None,
+            qual_offset: 33,
+            settings,
+            pe_stats,
+        })
+    }
+
+    /// The reference this aligner aligns to
+    pub fn reference(&self) -> &BwaReference {
+        &self.reference
+    }
+
+    /// Replace the paired-end model used to score read-pairs, e.g. with
+    /// one from `PairedEndStats::infer`
+    pub fn set_paired_end_stats(&mut self, pe_stats: PairedEndStats) {
+        self.pe_stats = pe_stats;
+    }
+
+    /// Set the read group of all reads aligned by this aligner, from a SAM header line
+    /// like `@RG\tID:foo\tSM:bar` (bwa mem -R). Aligned records are tagged with the
+    /// read group ID, and the `@RG` line is included in the header from `create_bam_header`.
+    pub fn set_read_group(&mut self, rg_line: &str) -> Result<(), ReadGroupError> {
+        self.read_group = Some(ReadGroup::parse(rg_line)?);
+        Ok(())
+    }
+
+    /// Use `read_group` for all reads aligned by this aligner, as for `set_read_group`
+    pub fn with_read_group(mut self, read_group: ReadGroup) -> BwaAligner {
+        self.read_group = Some(read_group);
+        self
+    }
+
+    /// Set the ASCII offset of the base qualities passed to this aligner, e.g. 64 for
+    /// legacy Phred+64 data. Qualities are converted to Phred+33 for BWA, so records
+    /// always have standard qualities. The default is 33. Offsets below 33 are rejected,
+    /// leaving the current offset in place.
+    pub fn set_qual_offset(&mut self, offset: u8) -> Result<(), AlignError> {
+        if offset < 33 {
+            return Err(AlignError::InvalidQualOffset(offset));
+        }
+        self.qual_offset = offset;
+        Ok(())
+    }
+
+    /// Copy `qual` into `buf` for BWA, converting it to Phred+33
+    fn fill_qual(&self, qual: &[u8], buf: &mut Vec<u8>) -> Result<(), AlignError> {
+        buf.clear();
+        if self.qual_offset == 33 {
+            buf.extend_from_slice(qual);
+            return Ok(());
+        }
+
+        for &q in qual {
+            if q < self.qual_offset {
+                return Err(AlignError::InvalidQual {
+                    qual: q,
+                    offset: self.qual_offset,
+                });
+            }
+            buf.push(q - self.qual_offset + 33);
+        }
+        Ok(())
+    }
+
+    /// Create a BAM header with the reference sequences, and the read group if one is set
+    #[cfg(feature = "htslib")]
+    pub fn create_bam_header(&self) -> Header {
+        let mut header = Header::new();
+        self.populate_bam_header(&mut header);
+        header
+    }
+
+    /// Add the reference sequences, and the read group if one is set, to `header`
+    #[cfg(feature = "htslib")]
+    pub fn populate_bam_header(&self, header: &mut Header) {
+        self.reference.populate_bam_header(header);
+
+        if let Some(ref rg) = self.read_group {
+            let mut header_rec = HeaderRecord::new(b"RG");
+            for (tag, value) in &rg.tags {
+                header_rec.push_tag(tag.as_bytes(), value);
+            }
+            header.push_record(&header_rec);
+        }
+    }
+
+    /// Add a `@PG` line recording this aligner to `header`, with the given `ID`, the
+    /// version of the bundled BWA, and the command line `cl`. If `cl` is `None`, the
+    /// `bwa mem` command line equivalent to the aligner's settings and read group is used.
+    #[cfg(feature = "htslib")]
+    pub fn populate_pg_header(&self, header: &mut Header, id: &str, cl: Option<&str>) {
+        let cl = cl.map_or_else(|| self.command_line(), |cl| cl.to_string());
+        push_pg_record(header, id, "bwa-mem (rust-bwa)", &cl);
+    }
+
+    /// Create a BAM header as with `create_bam_header`, plus a `@PG` line with `ID:bwa`
+    /// and `PN:bwa` recording the BWA version and the equivalent `bwa mem` command line,
+    /// as `bwa mem` itself writes.
+    #[cfg(feature = "htslib")]
+    pub fn create_bam_header_with_pg(&self) -> Header {
+        let mut header = self.create_bam_header();
+        push_pg_record(&mut header, "bwa", "bwa", &self.command_line());
+        header
+    }
+
+    /// The `bwa mem` command line equivalent to the settings and read group of the aligner
+    #[cfg(feature = "htslib")]
+    fn command_line(&self) -> String {
+        let mut cl = self.settings.command_line();
+        if let Some(ref rg) = self.read_group {
+            let rg_line: Vec<String> = rg.tags.iter().map(|t| format!("{}:{}", t.0, t.1)).collect();
+            cl.push_str(&format!(" -R '@RG\\t{}'", rg_line.join("\\t")));
+        }
+        cl
+    }
+
+    /// Create a BAM writer with the header from `create_bam_header_with_pg`
+    #[cfg(feature = "htslib")]
+    pub fn create_bam_writer<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<bam::Writer, rust_htslib::errors::Error> {
+        let header = self.create_bam_header_with_pg();
+        bam::Writer::from_path(path, &header, bam::Format::Bam)
+    }
+
+    /// Align a batch of read-pairs with `align_read_pairs`, using the thread count
+    /// of the aligner's settings, and write the records to `writer` in batch order.
+    #[cfg(feature = "htslib")]
+    pub fn align_and_write(
+        &self,
+        batch: &[ReadPair],
+        writer: &mut bam::Writer,
+    ) -> Result<(), AlignError> {
+        for (recs1, recs2) in self.align_read_pairs(batch, 0)? {
+            for rec in recs1.iter().chain(&recs2) {
+                writer.write(rec).map_err(AlignError::BamWrite)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Align a read-pair to the reference. Pass empty quality slices for reads
+    /// without base qualities, and the records will have `*` qualities.
+    ///
+    /// As with `bwa mem`, lowercase bases are treated as uppercase, and any base other
+    /// than A, C, G or T (such as IUPAC ambiguity codes) is treated as N. The sequences
+    /// of the records are normalized the same way.
+    ///
+    /// Each read-pair is aligned on the calling thread, ignoring the thread count of the
+    /// aligner's `BwaSettings` -- use `align_read_pairs` to align batches on worker threads.
+    ///
+    /// At least one record is always returned for each read. A read that doesn't align
+    /// is returned as a single record with the unmapped flag set. If its mate aligned,
+    /// the unmapped record is placed at the mate's position, as in `bwa mem` output;
+    /// otherwise its tid and position are -1.
+    ///
+    /// BWA breaks ties between equally good alignments pseudo-randomly, seeded by an id for
+    /// each read. The id is a hash of `name`, so results are deterministic, but reads with
+    /// different names may choose different copies of a repeat. See `align_read_pair_with_id`.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair(
+        &self,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+    ) -> Result<PairRecords, AlignError> {
+        self.align_read_pair_with_id(read_id(name), name, r1, q1, r2, q2)
+    }
+
+    /// Align a `ReadPair` as with `align_read_pair`
+    #[cfg(feature = "htslib")]
+    pub fn align(&self, pair: &ReadPair) -> Result<PairAlignment, AlignError> {
+        let (r1, r2) = self.align_read_pair(&pair.name, &pair.r1, &pair.q1, &pair.r2, &pair.q2)?;
+        Ok(PairAlignment { r1, r2 })
+    }
+
+    /// Align a read-pair as with `align_read_pair`, seeding BWA's choice between equally
+    /// good alignments with `id` rather than a hash of the read name. `bwa mem` uses the
+    /// index of each pair in the input, so passing it here reproduces its choices.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair_with_id(
+        &self,
+        id: i32,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+    ) -> Result<PairRecords, AlignError> {
+        let pair = PairInput {
+            id,
+            ..PairInput::new(name, r1, q1, r2, q2)
+        };
+        self.process_read_pair(&pair, |sam| self.parse_sam_to_records(sam))
+    }
+
+    /// Align a read-pair as with `align_read_pair`, appending `comment` to BWA's output for
+    /// both reads as `bwa mem -C` does with FASTQ comments. The comment must be tab-separated
+    /// SAM tags, such as `BX:Z:ACGT-1\tUB:Z:TTGA`, which become aux tags of every record of
+    /// the pair, or the records will fail to parse. An `RG` tag in the comment is kept in
+    /// place of the aligner's read group.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair_with_comment(
+        &self,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+        comment: Option<&[u8]>,
+    ) -> Result<PairRecords, AlignError> {
+        let pair = PairInput {
+            c1: comment,
+            c2: comment,
+            ..PairInput::new(name, r1, q1, r2, q2)
+        };
+        let mut scratch = AlignScratch::new();
+        self.run_read_pair(&mut scratch, &pair)?;
+        Ok((
+            self.parse_sam_to_records(&scratch.sam1)?,
+            self.parse_sam_to_records(&scratch.sam2)?,
+        ))
+    }
+
+    /// Align a `ReadPair` as with `align_read_pair`, adding the aux tags in `r1_tags` to every
+    /// record of read 1, and those in `r2_tags` to every record of read 2. Use this to keep
+    /// tags from an earlier step, such as `MM` and `ML` base modifications, attached to the
+    /// aligned reads. The tags are copied unchanged, so tags describing the whole read
+    /// sequence don't match supplementary records that BWA hard-clips; use
+    /// `set_softclip_supplementary` to keep the full sequence. Integers are stored in the
+    /// smallest type that holds them.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair_with_tags(
+        &self,
+        pair: &ReadPair,
+        r1_tags: &[([u8; 2], AuxValue)],
+        r2_tags: &[([u8; 2], AuxValue)],
+    ) -> Result<PairRecords, AlignError> {
+        let (mut recs1, mut recs2) =
+            self.align_read_pair(&pair.name, &pair.r1, &pair.q1, &pair.r2, &pair.q2)?;
+        sam::push_tags(&mut recs1, r1_tags)?;
+        sam::push_tags(&mut recs2, r2_tags)?;
+        Ok((recs1, recs2))
+    }
+
+    /// Align a read-pair as with `align_read_pair`, returning only the records that pass
+    /// `filter`. Records that are filtered out are never converted from BWA's SAM output,
+    /// so this is cheaper than filtering the results of `align_read_pair`. Unlike
+    /// `align_read_pair`, a read may have no records.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair_filtered(
+        &self,
+        filter: RecordFilter,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+    ) -> Result<PairRecords, AlignError> {
+        let pair = PairInput::new(name, r1, q1, r2, q2);
+        self.process_read_pair(&pair, |sam| self.parse_sam_to_records_filtered(sam, filter))
+    }
+
+    /// Align a read-pair as with `align_read_pair`, but convert BWA's SAM output to records
+    /// with htslib's SAM parser. Slower, as a header is created for each call, but useful
+    /// for checking the records produced by `align_read_pair`.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair_via_sam(
+        &self,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+    ) -> Result<PairRecords, AlignError> {
+        let pair = PairInput::new(name, r1, q1, r2, q2);
+        self.process_read_pair(&pair, |sam| self.parse_sam_to_records_htslib(sam))
+    }
+
+    /// Align a read-pair as with `align_read_pair`, returning each read's query sequence
+    /// exactly as submitted alongside its records. BWA reports the sequence of reads that
+    /// align to the reverse strand reverse-complemented, with ambiguous bases replaced by
+    /// `N`, so the submitted sequence can't always be recovered from the records alone.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair_with_query(
+        &self,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+    ) -> Result<(AlignedRead, AlignedRead), AlignError> {
+        let (recs1, recs2) = self.align_read_pair(name, r1, q1, r2, q2)?;
+        let read1 = AlignedRead {
+            query: r1.to_vec(),
+            records: recs1,
+        };
+        let read2 = AlignedRead {
+            query: r2.to_vec(),
+            records: recs2,
+        };
+        Ok((read1, read2))
+    }
+
+    /// Align a read-pair to the reference, returning only the position, mapping quality
+    /// and flags of each alignment. Much cheaper than `align_read_pair` when the full
+    /// BAM records aren't needed, as no htslib `Record`s are constructed.
+    pub fn align_read_pair_light(
+        &self,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+    ) -> Result<(Vec<BwaAlignment>, Vec<BwaAlignment>), AlignError> {
+        let pair = PairInput::new(name, r1, q1, r2, q2);
+        self.process_read_pair(&pair, |sam| self.parse_sam_to_alignments(sam))
+    }
+
+    /// Align a read-pair to the reference as with `align_read_pair`, returning plain
+    /// `SamRecordOwned`s parsed from BWA's SAM output rather than htslib `Record`s. This
+    /// is available without the `htslib` feature.
+    pub fn align_read_pair_raw(
+        &self,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+    ) -> Result<(Vec<SamRecordOwned>, Vec<SamRecordOwned>), AlignError> {
+        let pair = PairInput::new(name, r1, q1, r2, q2);
+        self.process_read_pair(&pair, |sam| {
+            raw::records_from_sam(&self.reference.contig_tids, sam, self.read_group_id())
+        })
+    }
+
+    /// Align a read-pair with BWA and convert the SAM output for each read with `parse`.
+    fn process_read_pair<T, F: Fn(&[u8]) -> Result<T, AlignError>>(
+        &self,
+        pair: &PairInput,
+        parse: F,
+    ) -> Result<(T, T), AlignError> {
+        let mut scratch = AlignScratch::new();
+        self.run_read_pair(&mut scratch, pair)?;
+        Ok((parse(&scratch.sam1)?, parse(&scratch.sam2)?))
+    }
+
+    /// Align a `ReadPair` as with `align_read_pair`, reusing the buffers in `scratch`
+    /// rather than allocating new copies of the reads and BWA's output for each call.
+    /// `out1` and `out2` are cleared and filled with the records of each read.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair_into(
+        &self,
+        scratch: &mut AlignScratch,
+        pair: &ReadPair,
+        out1: &mut Vec<Record>,
+        out2: &mut Vec<Record>,
+    ) -> Result<(), AlignError> {
+        out1.clear();
+        out2.clear();
+        self.run_read_pair(scratch, &PairInput::from(pair))?;
+
+        self.emit_records(&scratch.sam1, |rec| out1.push(rec))?;
+        self.emit_records(&scratch.sam2, |rec| out2.push(rec))
+    }
+
+    /// Align a read-pair as with `align_read_pair`, passing each record to `f` as soon as
+    /// it's converted rather than collecting them, e.g. to write them straight to a BAM
+    /// file. The records of read 1 are passed before those of read 2, which can be told
+    /// apart with `Record::is_first_in_template`.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pair_with<F: FnMut(Record)>(
+        &self,
+        name: &[u8],
+        r1: &[u8],
+        q1: &[u8],
+        r2: &[u8],
+        q2: &[u8],
+        mut f: F,
+    ) -> Result<(), AlignError> {
+        let mut scratch = AlignScratch::new();
+        self.run_read_pair(&mut scratch, &PairInput::new(name, r1, q1, r2, q2))?;
+        self.emit_records(&scratch.sam1, &mut f)?;
+        self.emit_records(&scratch.sam2, &mut f)
+    }
+
+    /// Convert BWA's SAM output for a read, passing each record to `f`
+    #[cfg(feature = "htslib")]
+    fn emit_records<F: FnMut(Record)>(&self, sam: &[u8], f: F) -> Result<(), AlignError> {
+        let parse_line = |line: &[u8]| sam::record_from_sam(&self.reference.contig_tids, line);
+        parse_sam_with(sam, self.read_group_id(), RecordFilter::All, &parse_line, f)
+    }
+
+    /// Align a read-pair with BWA, leaving the SAM output for each read in `scratch`
+    fn run_read_pair(
+        &self,
+        scratch: &mut AlignScratch,
+        pair: &PairInput,
+    ) -> Result<(), AlignError> {
+        let PairInput {
+            id,
+            name,
+            r1,
+            q1,
+            r2,
+            q2,
+            c1,
+            c2,
+        } = *pair;
+        check_lengths(r1, q1)?;
+        check_lengths(r2, q2)?;
+        self.fill_qual(q1, &mut scratch.q1)?;
+        self.fill_qual(q2, &mut scratch.q2)?;
+        fill_name(name, &mut scratch.name)?;
+
+        let c1_ptr = fill_comment(c1, &mut scratch.c1)?;
+        let c2_ptr = fill_comment(c2, &mut scratch.c2)?;
+
+        // Prep input data -- need to make copy of reads since BWA will edit the strings in-place
+        scratch.r1.clear();
+        scratch.r1.extend_from_slice(r1);
+        scratch.r2.clear();
+        scratch.r2.extend_from_slice(r2);
+
+        let read1 = bwa_sys::bseq1_t {
+            l_seq: r1.len() as i32,
+            name: scratch.name.as_mut_ptr() as *mut i8,
+            seq: scratch.r1.as_mut_ptr() as *mut i8,
+            qual: qual_ptr(&mut scratch.q1),
+            comment: c1_ptr,
+            id,
+            sam: ptr::null_mut(),
+        };
+
+        let read2 = bwa_sys::bseq1_t {
+            l_seq: r2.len() as i32,
+            name: scratch.name.as_mut_ptr() as *mut i8,
+            seq: scratch.r2.as_mut_ptr() as *mut i8,
+            qual: qual_ptr(&mut scratch.q2),
+            comment: c2_ptr,
+            id,
+            sam: ptr::null_mut(),
+        };
+
+        let mut reads = [read1, read2];
+
+        // Align the read pair. BWA will write the SAM data back to the bwa_sys::bseq1_t.sam field
+        unsafe {
+            let r = *(self.reference.bwt_data);
+            let settings = self.settings.bwa_settings;
+            bwa_sys::mem_process_seq_pe(
+                &settings,
+                r.bwt,
+                r.bns,
+                r.pac,
+                reads.as_mut_ptr(),
+                self.pe_stats.inner.as_ptr(),
+            );
+        }
+
+        // take both SAM outputs before checking for errors, so neither is leaked
+        let sam1 = take_sam_into(&mut reads[0], &mut scratch.sam1);
+        let sam2 = take_sam_into(&mut reads[1], &mut scratch.sam2);
+        sam1.and(sam2)
+    }
+
+    /// Align a single-end read with BWA, leaving its SAM output in `scratch.sam1`. The read
+    /// is aligned on the calling thread.
+    #[cfg(feature = "htslib")]
+    fn run_read(
+        &self,
+        scratch: &mut AlignScratch,
+        name: &[u8],
+        seq: &[u8],
+        qual: &[u8],
+        comment: Option<&[u8]>,
+    ) -> Result<(), AlignError> {
+        check_lengths(seq, qual)?;
+        self.fill_qual(qual, &mut scratch.q1)?;
+        fill_name(name, &mut scratch.name)?;
+        let comment = fill_comment(comment, &mut scratch.c1)?;
+        let id = read_id(name);
+
+        // Prep input data -- need to make copy of the read since BWA will edit the string in-place
+        scratch.r1.clear();
+        scratch.r1.extend_from_slice(seq);
+
+        let read = bwa_sys::bseq1_t {
+            l_seq: seq.len() as i32,
+            name: scratch.name.as_mut_ptr() as *mut i8,
+            seq: scratch.r1.as_mut_ptr() as *mut i8,
+            qual: qual_ptr(&mut scratch.q1),
+            comment,
+            id,
+            sam: ptr::null_mut(),
+        };
+
+        let mut reads = [read];
+
+        // Align the read in single-end mode. BWA will write the SAM data back to the
+        // bwa_sys::bseq1_t.sam field. mem_process_seqs seeds its choice between equally good
+        // alignments with `n_processed` plus the position of the read in the batch, rather than
+        // with `bseq1_t.id` (see `worker2` in bwamem.c), so the id is passed as `n_processed`.
+        unsafe {
+            let r = *(self.reference.bwt_data);
+            let mut settings = self.settings.bwa_settings;
+            settings.flag &= !((bwa_sys::MEM_F_PE | bwa_sys::MEM_F_SMARTPE) as i32);
+            settings.n_threads = 1;
+            bwa_sys::mem_process_seqs(
+                &settings,
+                r.bwt,
+                r.bns,
+                r.pac,
+                i64::from(id),
+                1,
+                reads.as_mut_ptr(),
+                ptr::null(),
+            );
+        }
+
+        take_sam_into(&mut reads[0], &mut scratch.sam1)
+    }
+
+    /// Align the read-pairs from a pair of FASTQ files, which may be gzip-compressed.
+    /// Returns an iterator over the records of each pair, in file order.
+    #[cfg(feature = "htslib")]
+    pub fn align_fastq<'a, P: AsRef<Path>>(
+        &'a self,
+        r1: P,
+        r2: P,
+    ) -> Result<impl Iterator<Item = Result<PairRecords, FastqError>> + 'a, FastqError> {
+        let pairs = FastqPairReader::from_paths(r1, r2)?;
+        Ok(self.align_fastq_pairs(pairs))
+    }
+
+    /// Align the read-pairs from an interleaved FASTQ file, which may be gzip-compressed.
+    /// Returns an iterator over the records of each pair, in file order.
+    #[cfg(feature = "htslib")]
+    pub fn align_interleaved_fastq<'a, P: AsRef<Path>>(
+        &'a self,
+        path: P,
+    ) -> Result<impl Iterator<Item = Result<PairRecords, FastqError>> + 'a, FastqError> {
+        let pairs = FastqPairReader::interleaved(path)?;
+        Ok(self.align_fastq_pairs(pairs))
+    }
+
+    /// Align the read-pairs from a pair of FASTQ files, which may be gzip-compressed, and
+    /// write the records to an unsorted BAM file at `out`, with the header from
+    /// `create_bam_writer`. Pairs are aligned in batches with `align_read_pairs`, using
+    /// `threads` worker threads, or the thread count of the aligner's settings if 0.
+    /// The size of the batches is set by `BwaSettings::set_chunk_size`, but as pairs are
+    /// aligned independently it doesn't change the output.
+    #[cfg(feature = "htslib")]
+    pub fn align_fastq_pair_to_bam<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        fq1: P,
+        fq2: P,
+        out: Q,
+        threads: usize,
+    ) -> Result<AlignStats, FastqError> {
+        let mut pairs = FastqPairReader::from_paths(fq1, fq2)?;
+        let mut writer = self.create_bam_writer(out).map_err(AlignError::BamWrite)?;
+        let mut stats = AlignStats::default();
+
+        let batch_threads = match threads {
+            0 => self.settings.bwa_settings.n_threads as i64,
+            n => n as i64,
+        };
+        let batch_bases = self.settings.bwa_settings.chunk_size as i64 * batch_threads;
+
+        let mut batch = Vec::new();
+        loop {
+            batch.clear();
+            let mut bases = 0;
+            while bases < batch_bases {
+                match pairs.next_pair()? {
+                    Some((r1, r2)) => {
+                        bases += (r1.seq.len() + r2.seq.len()) as i64;
+                        batch.push((r1, r2));
+                    }
+                    None => break,
+                }
+            }
+            if batch.is_empty() {
+                break;
+            }
+
+            let mut reads = Vec::with_capacity(batch.len() * 2);
+            for (r1, r2) in &batch {
+                let pair = self.fastq_input(r1, r2);
+                reads.push((pair.name, pair.r1, pair.q1, pair.c1));
+                reads.push((pair.name, pair.r2, pair.q2, pair.c2));
+            }
+
+            let recs = self.align_batch(&reads, bwa_sys::MEM_F_PE as i32, threads)?;
+            for (recs1, recs2) in into_pairs(recs) {
+                stats.add_pair(&recs1, &recs2);
+                for rec in recs1.iter().chain(&recs2) {
+                    writer.write(rec).map_err(AlignError::BamWrite)?;
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    #[cfg(feature = "htslib")]
+    fn align_fastq_pairs<'a>(
+        &'a self,
+        pairs: FastqPairReader,
+    ) -> impl Iterator<Item = Result<PairRecords, FastqError>> + 'a {
+        let mut scratch = AlignScratch::new();
+        pairs.map(move |pair| {
+            let (r1, r2) = pair?;
+            self.run_read_pair(&mut scratch, &self.fastq_input(&r1, &r2))?;
+            Ok((
+                self.parse_sam_to_records(&scratch.sam1)?,
+                self.parse_sam_to_records(&scratch.sam2)?,
+            ))
+        })
+    }
+
+    /// The input to BWA for a pair of FASTQ records, with their comments if the settings
+    /// append them
+    #[cfg(feature = "htslib")]
+    fn fastq_input<'b>(&self, r1: &'b FastqRecord, r2: &'b FastqRecord) -> PairInput<'b> {
+        let mut pair = PairInput::new(&r1.name, &r1.seq, &r1.qual, &r2.seq, &r2.qual);
+        if self.settings.append_comment {
+            pair.c1 = r1.comment.as_deref();
+            pair.c2 = r2.comment.as_deref();
+        }
+        pair
+    }
+
+    /// Align a single-end read to the reference. The paired-end flags are never set on
+    /// the returned records, and the `PairedEndStats` of the aligner are not used.
+    /// Chimeric reads return the primary record followed by any supplementary records.
+    /// As with `align_read_pair`, `qual` may be empty if the read has no base qualities,
+    /// and ties between equally good alignments are broken by a hash of `name`.
+    #[cfg(feature = "htslib")]
+    pub fn align_read(
+        &self,
+        name: &[u8],
+        seq: &[u8],
+        qual: &[u8],
+    ) -> Result<Vec<Record>, AlignError> {
+        let mut scratch = AlignScratch::new();
+        self.run_read(&mut scratch, name, seq, qual, None)?;
+        self.parse_sam_to_records(&scratch.sam1)
+    }
+
+    /// Find the alignment regions of a single-end read, as scored by BWA before any
+    /// conversion to SAM records. Regions are sorted by decreasing score, and include
+    /// low-scoring regions that BWA would not report as alignments.
+    pub fn align_read_regions(&self, seq: &[u8]) -> Vec<AlignmentRegion> {
+        let regs = self.find_regions(seq);
+
+        let mut regions = Vec::with_capacity(regs.n as usize);
+        for i in 0..regs.n as isize {
+            let reg = unsafe { *regs.a.offset(i) };
+            regions.push(self.convert_region(&reg));
+        }
+
+        unsafe {
+            libc::free(regs.a as *mut libc::c_void);
+        }
+
+        regions
+    }
+
+    /// Run BWA's first alignment pass on a read. The caller must free the returned regions.
+    fn find_regions(&self, seq: &[u8]) -> bwa_sys::mem_alnreg_v {
+        let mut settings = self.settings.bwa_settings;
+        settings.flag &= !(bwa_sys::MEM_F_PE as i32);
+
+        // mem_align1 copies the sequence before converting it, so no copy is needed here
+        unsafe {
+            let r = *(self.reference.bwt_data);
+            bwa_sys::mem_align1(
+                &settings,
+                r.bwt,
+                r.bns,
+                r.pac,
+                seq.len() as i32,
+                seq.as_ptr() as *const i8,
+            )
+        }
+    }
+
+    /// Convert a region from BWA's concatenated forward-reverse coordinates
+    /// to forward-strand contig coordinates
+    fn convert_region(&self, reg: &bwa_sys::mem_alnreg_t) -> AlignmentRegion {
+        let (l_pac, offset) = unsafe {
+            let bns = (*self.reference.bwt_data).bns;
+            ((*bns).l_pac, (*(*bns).anns.offset(reg.rid as isize)).offset)
+        };
+
+        let is_reverse = reg.rb >= l_pac;
+        let (rb, re) = if is_reverse {
+            ((l_pac << 1) - reg.re, (l_pac << 1) - reg.rb)
+        } else {
+            (reg.rb, reg.re)
+        };
+
+        AlignmentRegion {
+            tid: reg.rid,
+            ref_start: rb - offset,
+            ref_end: re - offset,
+            is_reverse,
+            query_start: reg.qb,
+            query_end: reg.qe,
+            score: reg.score,
+            sub_score: reg.sub,
+            is_secondary: reg.secondary >= 0,
+            // as computed by mem_reg2aln
+            mapq_estimate: if reg.secondary < 0 {
+                unsafe { bwa_sys::mem_approx_mapq_se(&self.settings.bwa_settings, reg) as u8 }
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Align a batch of read-pairs to the reference. The batch is spread over `threads` worker threads; pass 0 to use the thread count of
+    /// the aligner's `BwaSettings`. Each pair is aligned as by `align_read_pair`, so the
+    /// results don't depend on the batch size, the order of the batch or the thread count.
+    /// The records for each pair are returned in batch order.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pairs(
+        &self,
+        batch: &[ReadPair],
+        threads: usize,
+    ) -> Result<Vec<PairRecords>, AlignError> {
+        let mut reads = Vec::with_capacity(batch.len() * 2);
+        for pair in batch {
+            reads.push((&pair.name[..], &pair.r1[..], &pair.q1[..], None));
+            reads.push((&pair.name[..], &pair.r2[..], &pair.q2[..], None));
+        }
+
+        let recs = self.align_batch(&reads, bwa_sys::MEM_F_PE as i32, threads)?;
+        Ok(into_pairs(recs))
+    }
+
+    /// Align the read-pairs of `pairs` in batches of `batch` pairs with `align_read_pairs`,
+    /// using the thread count of the aligner's settings. Returns an iterator over the
+    /// alignment of each pair, in input order. A failed batch is reported as a single
+    /// error, after which alignment continues with the next batch.
+    #[cfg(feature = "htslib")]
+    pub fn align_pairs_from<'a, I>(
+        &'a self,
+        pairs: I,
+        batch: usize,
+    ) -> impl Iterator<Item = Result<PairAlignment, AlignError>> + 'a
+    where
+        I: IntoIterator<Item = ReadPair>,
+        I::IntoIter: 'a,
+    {
+        let mut pairs = pairs.into_iter();
+        let batch_size = batch.max(1);
+        let mut aligned = Vec::new().into_iter();
+
+        std::iter::from_fn(move || {
+            if let Some((r1, r2)) = aligned.next() {
+                return Some(Ok(PairAlignment { r1, r2 }));
+            }
+
+            let batch: Vec<_> = pairs.by_ref().take(batch_size).collect();
+            if batch.is_empty() {
+                return None;
+            }
+
+            match self.align_read_pairs(&batch, 0) {
+                Ok(recs) => {
+                    aligned = recs.into_iter();
+                    aligned.next().map(|(r1, r2)| Ok(PairAlignment { r1, r2 }))
+                }
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
+
+    /// Align `pairs` in parallel on Rayon's thread pool rather than on threads started for
+    /// each batch. The pairs are split into chunks of `PAR_CHUNK_PAIRS`, and each chunk is
+    /// aligned on its Rayon worker with `align_read_pairs`. This relies on the loaded
+    /// `BwaReference` being read-only, so `&self` can be shared between the Rayon workers,
+    /// and on records being built without a `HeaderView`, so the workers don't contend on a
+    /// lock. The alignments are returned in input order, and match those of `align`.
+    #[cfg(all(feature = "htslib", feature = "rayon"))]
+    pub fn align_pairs_par(&self, pairs: &[ReadPair]) -> Result<Vec<PairAlignment>, AlignError> {
+        use rayon::prelude::*;
+
+        let chunks: Vec<Vec<PairRecords>> = pairs
+            .par_chunks(PAR_CHUNK_PAIRS)
+            .map(|chunk| self.align_read_pairs(chunk, 1))
+            .collect::<Result<_, _>>()?;
+
+        Ok(chunks
+            .into_iter()
+            .flatten()
+            .map(|(r1, r2)| PairAlignment { r1, r2 })
+            .collect())
+    }
+
+    /// Align a batch of read-pairs as with `align_read_pairs`, also returning metrics of
+    /// the alignments, with an insert size histogram up to `max_insert_size`.
+    #[cfg(feature = "htslib")]
+    pub fn align_read_pairs_with_metrics(
+        &self,
+        batch: &[ReadPair],
+        threads: usize,
+        max_insert_size: usize,
+    ) -> Result<(Vec<PairRecords>, AlignmentMetrics), AlignError> {
+        let pairs = self.align_read_pairs(batch, threads)?;
+        let mut metrics = AlignmentMetrics::new(max_insert_size);
+        for (recs1, recs2) in &pairs {
+            metrics.add_pair(recs1, recs2);
+        }
+        Ok((pairs, metrics))
+    }
+
+    /// Align a batch of single-end reads to the reference. Each batch entry is
+    /// `(name, seq, qual)`. The batch is spread over `threads` worker threads; pass 0 to
+    /// use the thread count of the aligner's `BwaSettings`. Each read is aligned as by
+    /// `align_read`, so the results don't depend on the batch size, the order of the batch
+    /// or the thread count. The records for each read are returned in batch order.
+    #[cfg(feature = "htslib")]
+    pub fn align_reads(
+        &self,
+        batch: &[(Vec<u8>, Vec<u8>, Vec<u8>)],
+        threads: usize,
+    ) -> Result<Vec<Vec<Record>>, AlignError> {
+        let reads: Vec<_> = batch
+            .iter()
+            .map(|(name, seq, qual)| (&name[..], &seq[..], &qual[..], None))
+            .collect();
+        self.align_batch(&reads, 0, threads)
+    }
+
+    /// Align an interleaved batch of paired reads, where each read 1 is followed by its
+    /// read 2, as `bwa mem -p` does. Each batch entry is `(name, seq, qual)`, and the batch
+    /// must hold an even number of reads. Otherwise the same as `align_read_pairs`.
+    #[cfg(feature = "htslib")]
+    pub fn align_interleaved(
+        &self,
+        batch: &[(Vec<u8>, Vec<u8>, Vec<u8>)],
+        threads: usize,
+    ) -> Result<Vec<PairRecords>, AlignError> {
+        if !batch.len().is_multiple_of(2) {
+            return Err(AlignError::OddInterleavedBatch(batch.len()));
+        }
+
+        let reads: Vec<_> = batch
+            .iter()
+            .map(|(name, seq, qual)| (&name[..], &seq[..], &qual[..], None))
+            .collect();
+        let flags = (bwa_sys::MEM_F_PE | bwa_sys::MEM_F_SMARTPE) as i32;
+        let recs = self.align_batch(&reads, flags, threads)?;
+        Ok(into_pairs(recs))
+    }
+
+    /// Align a batch of `(name, seq, qual, comment)` reads, with the given `flags` set in place
+    /// of the paired-end flags of the settings. Consecutive reads are aligned as mates when
+    /// `MEM_F_PE` is set -- with `MEM_F_SMARTPE` also set, only if their names match. Returns
+    /// the records of each read.
+    ///
+    /// Batches aren't passed to `mem_process_seqs` whole: it seeds BWA's choice between
+    /// equally good alignments with the position of each read in the batch, so results would
+    /// depend on how reads were batched. Instead each pair goes through `run_read_pair`, and
+    /// each single-end read through `run_read`, both seeded with a hash of the read name.
+    /// The batch is split into contiguous chunks over `threads` worker threads, or the thread
+    /// count of the settings if `threads` is 0.
+    #[cfg(feature = "htslib")]
+    fn align_batch(
+        &self,
+        batch: &[BatchRead],
+        flags: i32,
+        threads: usize,
+    ) -> Result<Vec<Vec<Record>>, AlignError> {
+        let paired = flags & bwa_sys::MEM_F_PE as i32 != 0;
+        let smart = flags & bwa_sys::MEM_F_SMARTPE as i32 != 0;
+
+        // Split the batch into the units aligned by a single call into BWA
+        let mut units = Vec::with_capacity(batch.len());
+        let mut i = 0;
+        while i < batch.len() {
+            let n = if paired && i + 1 < batch.len() && (!smart || batch[i].0 == batch[i + 1].0) {
+                2
+            } else {
+                1
+            };
+            units.push(&batch[i..i + n]);
+            i += n;
+        }
+
+        let threads = match threads {
+            0 => self.settings.bwa_settings.n_threads.max(1) as usize,
+            n => n,
+        };
+        let chunk_size = units.len().div_ceil(threads).max(1);
+
+        let align_chunk = |chunk: &[&[BatchRead]]| {
+            let mut scratch = AlignScratch::new();
+            let mut recs = Vec::new();
+            for unit in chunk {
+                match **unit {
+                    [(name, r1, q1, c1), (_, r2, q2, c2)] => {
+                        let pair = PairInput {
+                            c1,
+                            c2,
+                            ..PairInput::new(name, r1, q1, r2, q2)
+                        };
+                        self.run_read_pair(&mut scratch, &pair)?;
+                        recs.push(self.parse_sam_to_records(&scratch.sam1)?);
+                        recs.push(self.parse_sam_to_records(&scratch.sam2)?);
+                    }
+                    _ => {
+                        for &(name, seq, qual, comment) in *unit {
+                            self.run_read(&mut scratch, name, seq, qual, comment)?;
+                            recs.push(self.parse_sam_to_records(&scratch.sam1)?);
+                        }
+                    }
+                }
+            }
+            Ok(recs)
+        };
+
+        if units.len() <= chunk_size {
+            return align_chunk(&units);
+        }
+
+        let chunks: Vec<Result<Vec<Vec<Record>>, AlignError>> = std::thread::scope(|scope| {
+            let workers: Vec<_> = units
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || align_chunk(chunk)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|w| w.join().expect("alignment worker panicked"))
+                .collect()
+        });
+
+        let mut recs = Vec::with_capacity(batch.len());
+        for chunk in chunks {
+            recs.extend(chunk?);
+        }
+        Ok(recs)
+    }
+
+    #[cfg(feature = "htslib")]
+    fn parse_sam_to_records(&self, sam: &[u8]) -> Result<Vec<Record>, AlignError> {
+        self.parse_sam_to_records_filtered(sam, RecordFilter::All)
+    }
+
+    #[cfg(feature = "htslib")]
+    fn parse_sam_to_records_filtered(
+        &self,
+        sam: &[u8],
+        filter: RecordFilter,
+    ) -> Result<Vec<Record>, AlignError> {
+        parse_sam_to_records(sam, self.read_group_id(), filter, |line| {
+            sam::record_from_sam(&self.reference.contig_tids, line)
+        })
+    }
+
+    /// Parse SAM output with htslib's SAM parser. `Record::from_sam` mutates the header
+    /// it's given, so a new header is created for each call rather than sharing one
+    /// between threads.
+    #[cfg(feature = "htslib")]
+    fn parse_sam_to_records_htslib(&self, sam: &[u8]) -> Result<Vec<Record>, AlignError> {
+        let header_view = HeaderView::from_header(&self.reference.create_bam_header());
+        parse_sam_to_records(sam, self.read_group_id(), RecordFilter::All, |line| {
+            Record::from_sam(&header_view, line).map_err(|e| AlignError::SamParse {
+                line: String::from_utf8_lossy(line).into_owned(),
+                source: e,
+            })
+        })
+    }
+
+    fn read_group_id(&self) -> Option<&str> {
+        self.read_group.as_ref().map(|rg| rg.id.as_str())
+    }
+
+    fn parse_sam_to_alignments(&self, sam: &[u8]) -> Result<Vec<BwaAlignment>, AlignError> {
+        let mut alignments = Vec::new();
+
+        for slc in sam.split(|x| *x == b'\n') {
+            if !slc.is_empty() {
+                alignments.push(BwaAlignment::from_sam(&self.reference.contig_tids, slc)?);
+            }
+        }
+
+        Ok(alignments)
+    }
+}
+
+/// An alignment region of a read found by BWA, prior to SAM formatting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlignmentRegion {
+    /// Reference sequence id
+    pub tid: i32,
+    /// 0-based start of the aligned region on the forward strand of the reference
+    pub ref_start: i64,
+    /// 0-based exclusive end of the aligned region on the forward strand of the reference
+    pub ref_end: i64,
+    pub is_reverse: bool,
+    /// 0-based start of the aligned part of the read
+    pub query_start: i32,
+    /// 0-based exclusive end of the aligned part of the read
+    pub query_end: i32,
+    /// Best local Smith-Waterman score
+    pub score: i32,
+    /// Second-best Smith-Waterman score
+    pub sub_score: i32,
+    /// Whether the region is shadowed by a better-scoring region
+    pub is_secondary: bool,
+    /// The single-end mapping quality BWA gives a record of the region, which is 0 for
+    /// secondary regions. The mapping quality of a supplementary record is capped at
+    /// that of the primary.
+    pub mapq_estimate: u8,
+}
+
+/// The records of a read, along with its query sequence as it was submitted for alignment
+#[cfg(feature = "htslib")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignedRead {
+    /// The read sequence in its original orientation and case
+    pub query: Vec<u8>,
+    pub records: Vec<Record>,
+}
+
+#[cfg(feature = "htslib")]
+impl AlignedRead {
+    /// Whether BWA reverse-complemented the query to produce the `i`th record, i.e. whether
+    /// the record's sequence is the reverse complement of `query`.
+    pub fn is_reverse(&self, i: usize) -> bool {
+        self.records[i].is_reverse()
+    }
+}
+
+/// Reusable buffers for `BwaAligner::align_read_pair_into`, holding the copies of each
+/// read passed to BWA and BWA's SAM output. A scratch can be used with any aligner, but
+/// only by one thread at a time.
+#[derive(Debug, Default)]
+pub struct AlignScratch {
+    name: Vec<u8>,
+    c1: Vec<u8>,
+    c2: Vec<u8>,
+    r1: Vec<u8>,
+    q1: Vec<u8>,
+    r2: Vec<u8>,
+    q2: Vec<u8>,
+    sam1: Vec<u8>,
+    sam2: Vec<u8>,
+}
+
+impl AlignScratch {
+    pub fn new() -> AlignScratch {
+        AlignScratch::default()
+    }
+}
+
+/// The records of read 1 and read 2 of an aligned read-pair
+#[cfg(feature = "htslib")]
+pub type PairRecords = (Vec<Record>, Vec<Record>);
+
+/// A read as passed to `BwaAligner::align_batch`: `(name, seq, qual, comment)`
+#[cfg(feature = "htslib")]
+type BatchRead<'a> = (&'a [u8], &'a [u8], &'a [u8], Option<&'a [u8]>);
+
+/// A read-pair as passed to BWA by `BwaAligner::run_read_pair`. `id` seeds BWA's choice
+/// between equally good alignments, and `c1` and `c2` are appended to the SAM output of
+/// each read.
+#[derive(Clone, Copy)]
+struct PairInput<'a> {
+    id: i32,
+    name: &'a [u8],
+    r1: &'a [u8],
+    q1: &'a [u8],
+    r2: &'a [u8],
+    q2: &'a [u8],
+    c1: Option<&'a [u8]>,
+    c2: Option<&'a [u8]>,
+}
+
+impl<'a> PairInput<'a> {
+    /// A read-pair without comments, with an id hashed from its name
+    fn new(name: &'a [u8], r1: &'a [u8], q1: &'a [u8], r2: &'a [u8], q2: &'a [u8]) -> Self {
+        PairInput {
+            id: read_id(name),
+            name,
+            r1,
+            q1,
+            r2,
+            q2,
+            c1: None,
+            c2: None,
+        }
+    }
+}
+
+impl<'a> From<&'a ReadPair> for PairInput<'a> {
+    fn from(pair: &'a ReadPair) -> Self {
+        PairInput::new(&pair.name, &pair.r1, &pair.q1, &pair.r2, &pair.q2)
+    }
+}
+
+/// A read-pair to align with `BwaAligner::align`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadPair {
+    pub name: Vec<u8>,
+    pub r1: Vec<u8>,
+    pub q1: Vec<u8>,
+    pub r2: Vec<u8>,
+    pub q2: Vec<u8>,
+}
+
+impl From<(fastq::FastqRecord, fastq::FastqRecord)> for ReadPair {
+    /// A read-pair from FASTQ records, named after read 1
+    fn from((rec1, rec2): (fastq::FastqRecord, fastq::FastqRecord)) -> ReadPair {
+        ReadPair {
+            name: rec1.name,
+            r1: rec1.seq,
+            q1: rec1.qual,
+            r2: rec2.seq,
+            q2: rec2.qual,
+        }
+    }
+}
+
+/// A supermaximal exact match between a read and the reference, found by
+/// `BwaReference::find_smems`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Smem {
+    query_start: usize,
+    query_end: usize,
+    occurrences: u64,
+    sa_begin: u64,
+}
+
+impl Smem {
+    /// 0-based start of the match in the read
+    pub fn query_start(&self) -> usize {
+        self.query_start
+    }
+
+    /// 0-based, exclusive end of the match in the read
+    pub fn query_end(&self) -> usize {
+        self.query_end
+    }
+
+    /// Number of occurrences of the match in the reference, on either strand
+    pub fn occurrences(&self) -> u64 {
+        self.occurrences
+    }
+
+    /// Start of the interval of the suffix array holding the occurrences, which are
+    /// located by `BwaReference::smem_hits`
+    pub fn sa_begin(&self) -> u64 {
+        self.sa_begin
+    }
+}
+
+/// An occurrence of an SMEM in the reference, found by `BwaReference::smem_hits`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmemHit {
+    pub tid: i32,
+    /// 0-based leftmost position of the match on the forward strand
+    pub pos: i64,
+    pub is_reverse: bool,
+}
+
+/// The records of a read-pair aligned with `BwaAligner::align`
+#[cfg(feature = "htslib")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairAlignment {
+    pub r1: Vec<Record>,
+    pub r2: Vec<Record>,
+}
+
+#[cfg(feature = "htslib")]
+impl PairAlignment {
+    /// The primary record of read 1, which may be unmapped
+    pub fn primary_r1(&self) -> &Record {
+        primary(&self.r1)
+    }
+
+    /// The primary record of read 2, which may be unmapped
+    pub fn primary_r2(&self) -> &Record {
+        primary(&self.r2)
+    }
+}
+
+/// BWA writes the primary record of a read first, and always writes at least one record
+#[cfg(feature = "htslib")]
+fn primary(recs: &[Record]) -> &Record {
+    recs.iter()
+        .find(|rec| !rec.is_secondary() && !rec.is_supplementary())
+        .unwrap_or(&recs[0])
+}
+
+/// Selects which records are returned by `BwaAligner::align_read_pair_filtered`
+#[cfg(feature = "htslib")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFilter {
+    /// Every record, as returned by `align_read_pair`
+    All,
+    /// Only the primary record of each read, without secondary (0x100) or
+    /// supplementary (0x800) records. Unmapped reads are kept.
+    PrimaryOnly,
+    /// Only mapped records
+    MappedOnly,
+    /// Only mapped records with at least the given mapping quality
+    MapqAtLeast(u8),
+}
+
+#[cfg(feature = "htslib")]
+impl RecordFilter {
+    /// Whether to keep the record of a line of SAM output. Lines that can't be parsed
+    /// are kept, so the error is reported when converting them.
+    fn keep(self, line: &[u8]) -> bool {
+        if self == RecordFilter::All {
+            return true;
+        }
+
+        let mut fields = line.split(|c| *c == b'\t');
+        let flags = fields
+            .nth(1)
+            .and_then(|f| std::str::from_utf8(f).ok()?.parse::<u16>().ok());
+        let mapq = fields
+            .nth(2)
+            .and_then(|f| std::str::from_utf8(f).ok()?.parse::<u8>().ok());
+        let (flags, mapq) = match (flags, mapq) {
+            (Some(flags), Some(mapq)) => (flags, mapq),
+            _ => return true,
+        };
+
+        match self {
+            RecordFilter::All => true,
+            RecordFilter::PrimaryOnly => flags & (0x100 | 0x800) == 0,
+            RecordFilter::MappedOnly => flags & 0x4 == 0,
+            RecordFilter::MapqAtLeast(min_mapq) => flags & 0x4 == 0 && mapq >= min_mapq,
+        }
+    }
+}
+
+/// A lightweight summary of an alignment, without the sequence, qualities
+/// or tags carried by a full BAM `Record`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BwaAlignment {
+    /// Reference sequence id, or -1 if unmapped
+    pub tid: i32,
+    /// 0-based leftmost position, or -1 if unmapped
+    pub pos: i64,
+    pub mapq: u8,
+    /// SAM flags
+    pub flags: u16,
+    /// Number of CIGAR operations
+    pub cigar_len: u32,
+}
+
+impl BwaAlignment {
+    /// Secondary alignment (flag 0x100)
+    pub fn is_secondary(&self) -> bool {
+        self.flags & 0x100 != 0
+    }
+
+    fn from_sam(
+        contig_tids: &HashMap<Vec<u8>, i32>,
+        sam: &[u8],
+    ) -> Result<BwaAlignment, AlignError> {
+        let malformed = || AlignError::MalformedSam(String::from_utf8_lossy(sam).into_owned());
+        let fields: Vec<&[u8]> = sam.splitn(7, |x| *x == b'\t').collect();
+        if fields.len() < 6 {
+            return Err(malformed());
+        }
+
+        let flags = raw::parse_num(fields[1]).ok_or_else(malformed)?;
+        let tid = raw::lookup_tid(contig_tids, fields[2]).ok_or_else(malformed)?;
+        let pos = raw::parse_num::<i64>(fields[3]).ok_or_else(malformed)? - 1;
+        let mapq = raw::parse_num(fields[4]).ok_or_else(malformed)?;
+        let cigar_len = fields[5]
+            .iter()
+            .filter(|c| c.is_ascii_alphabetic() || **c == b'=')
+            .count() as u32;
+
+        Ok(BwaAlignment {
+            tid,
+            pos,
+            mapq,
+            flags,
+            cigar_len,
+        })
+    }
+}
+
+#[cfg(feature = "htslib")]
+fn parse_sam_to_records<F>(
+    sam: &[u8],
+    read_group: Option<&str>,
+    filter: RecordFilter,
+    parse_line: F,
+) -> Result<Vec<Record>, AlignError>
+where
+    F: Fn(&[u8]) -> Result<Record, AlignError>,
+{
+    let mut records = Vec::new();
+    parse_sam_with(sam, read_group, filter, &parse_line, |rec| {
+        records.push(rec)
+    })?;
+    Ok(records)
+}
+
+/// Convert each line of SAM output that passes `filter` with `parse_line`, passing
+/// the records to `f`
+#[cfg(feature = "htslib")]
+fn parse_sam_with<F, G>(
+    sam: &[u8],
+    read_group: Option<&str>,
+    filter: RecordFilter,
+    parse_line: &F,
+    mut f: G,
+) -> Result<(), AlignError>
+where
+    F: Fn(&[u8]) -> Result<Record, AlignError>,
+    G: FnMut(Record),
+{
+    let mut n_lines = 0;
+
+    for slc in sam.split(|x| *x == b'\n') {
+        if !slc.is_empty() {
+            n_lines += 1;
+            if !filter.keep(slc) {
+                continue;
+            }
+            let mut record = parse_line(slc)?;
+            // an RG tag passed through from a read comment takes precedence
+            if let (Some(rg), Err(_)) = (read_group, record.aux(b"RG")) {
+                record
+                    .push_aux(b"RG", Aux::String(rg))
+                    .map_err(|e| AlignError::InvalidTag {
+                        tag: "RG".to_string(),
+                        msg: e.to_string(),
+                    })?;
+            }
+            f(record);
+        }
+    }
+
+    // BWA always writes a record for each read, even if it's unmapped
+    if n_lines == 0 {
+        return Err(AlignError::NoOutput);
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "htslib"))]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use rust_htslib::bam::record::Cigar;
+    use rust_htslib::bam::Read;
+    use std::collections::HashSet;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn load_aligner() -> BwaAligner {
+        let aln = BwaAligner::from_path("tests/test_ref.fa");
+        aln.unwrap()
+    }
+
+    #[test]
+    fn sam_parse_error() {
+        let bwa = load_aligner();
+        let sam = b"read1\t0\tchr\tnot_a_position\t60\t4M\t*\t0\t0\tACGT\tIIII\n";
+        match bwa.parse_sam_to_records(sam) {
+            Err(AlignError::SamParse { line, .. }) => assert!(line.contains("not_a_position")),
+            res => panic!("expected SAM parse error, got {:?}", res),
+        }
+
+        let err: BwaError = bwa.parse_sam_to_records(sam).unwrap_err().into();
+        assert!(err.to_string().contains("not_a_position"));
+
+        // a contig that isn't in the reference, and a stray carriage return
+        let bad: &[&[u8]] = &[
+            b"read1\t0\tchrUn\t100\t60\t4M\t*\t0\t0\tACGT\tIIII\n",
+            b"read1\t0\tchr\t100\t60\t4M\t*\t0\t0\tACGT\tIIII\r\n",
+        ];
+        for sam in bad {
+            match bwa.parse_sam_to_records(sam) {
+                Err(AlignError::SamParse { line, .. }) => {
+                    assert_eq!(line.as_bytes(), &sam[..sam.len() - 1])
+                }
+                res => panic!("expected SAM parse error, got {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn silent_at_verbosity_0() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-verbosity-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let stderr_path = dir.join("stderr.txt");
+
+        set_verbosity(0);
+
+        // redirect stderr to a file while loading the reference and aligning, both of
+        // which write progress messages at the default verbosity
+        let stderr_file = File::create(&stderr_path).unwrap();
+        let saved_stderr = unsafe { libc::dup(2) };
+        unsafe {
+            libc::dup2(std::os::unix::io::AsRawFd::as_raw_fd(&stderr_file), 2);
+        }
+
+        let bwa = load_aligner();
+        let batch = vec![to_read_pair(read_simple()), to_read_pair(read_split())];
+        let results = bwa.align_read_pairs(&batch, 1);
+        PairedEndStats::infer(&bwa, &batch);
+
+        unsafe {
+            libc::dup2(saved_stderr, 2);
+            libc::close(saved_stderr);
+        }
+        set_verbosity(3);
+
+        assert!(results.is_ok());
+        assert_eq!(std::fs::read(&stderr_path).unwrap(), b"");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unmapped_pair() {
+        let bwa = load_aligner();
+        let n = [b'N'; 100];
+        let (r1, r2) = bwa.align_read_pair(b"unmapped", &n, b"", &n, b"").unwrap();
+        for recs in &[r1, r2] {
+            assert_eq!(recs.len(), 1);
+            assert!(recs[0].is_unmapped());
+            assert_eq!(recs[0].tid(), -1);
+            assert_eq!(recs[0].pos(), -1);
+        }
+
+        // an unmapped read with a mapped mate is placed at its mate's position
+        let r = read_simple();
+        let (r1, r2) = bwa.align_read_pair(b"half", r[1], r[2], &n, b"").unwrap();
+        assert!(!r1[0].is_unmapped());
+        assert_eq!(r2.len(), 1);
+        assert!(r2[0].is_unmapped());
+        assert!(r1[0].is_mate_unmapped());
+        assert_eq!((r2[0].tid(), r2[0].pos()), (r1[0].tid(), r1[0].pos()));
+
+        assert!(bwa.parse_sam_to_records(b"").is_err());
+    }
+
+    #[test]
+    fn settings_builder() {
+        let settings = BwaSettingsBuilder::new()
+            .scores(2, 8, 12, 2)
+            .band_width(200)
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let s = &settings.bwa_settings;
+        assert_eq!(
+            (s.a, s.b, s.o_del, s.e_ins, s.w, s.n_threads),
+            (2, 8, 12, 2, 200, 4)
+        );
+        assert_eq!((s.mat[0], s.mat[1]), (2, -8));
+
+        let err = BwaSettingsBuilder::new()
+            .scores(-1, 4, 6, 1)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "match score must be positive, got -1");
+        assert_eq!((err.param, err.value.as_str()), ("match score", "-1"));
+        assert!(BwaSettingsBuilder::new().num_threads(0).build().is_err());
+        assert!(BwaSettingsBuilder::new().split_factor(0.0).build().is_err());
+        assert!(BwaSettingsBuilder::new().drop_ratio(1.5).build().is_err());
+
+        let err: BwaError = BwaSettingsBuilder::new()
+            .band_width(-5)
+            .build()
+            .err()
+            .unwrap()
+            .into();
+        assert!(err.to_string().contains("band width"));
+    }
+
+    #[test]
+    fn settings_setters() {
+        let settings = BwaSettings::new()
+            .set_scores(2, 8, 12, 2)
+            .set_clip_scores(0, 0)
+            .set_unpaired(0)
+            .set_min_output_score(20)
+            .set_band_width(50)
+            .set_max_insert(1000)
+            .set_zdrop(200)
+            .set_min_seed_len(15)
+            .set_split_factor(2.0)
+            .set_drop_ratio(0.0)
+            .set_max_xa_hits(0, 10)
+            .set_xa_drop_ratio(1.0)
+            .set_split_width(20)
+            .set_max_occ(1000)
+            .set_num_threads(2)
+            .set_chunk_size(1000);
+        let p = settings.params();
+        assert_eq!(
+            (p.a, p.b, p.pen_clip5, p.T, p.w, p.max_occ),
+            (2, 8, 0, 20, 50, 1000)
+        );
+    }
+
+    // The setters only check their arguments in debug builds
+    #[cfg(debug_assertions)]
+    #[test]
+    fn settings_setters_rejected() {
+        // the parameter named in the panic, and a setter call with an invalid value for it
+        type InvalidSetting = (&'static str, fn() -> BwaSettings);
+        let invalid: &[InvalidSetting] = &[
+            ("match score", || BwaSettings::new().set_scores(0, 4, 6, 1)),
+            ("mismatch penalty", || {
+                BwaSettings::new().set_scores(1, -5, 6, 1)
+            }),
+            ("gap open penalty", || {
+                BwaSettings::new().set_scores(1, 4, -1, 1)
+            }),
+            ("gap extension penalty", || {
+                BwaSettings::new().set_scores(1, 4, 6, 0)
+            }),
+            ("deletion open penalty", || {
+                BwaSettings::new().set_gap_penalties(-1, 1, 6, 1)
+            }),
+            ("insertion extension penalty", || {
+                BwaSettings::new().set_gap_penalties(6, 1, 6, 0)
+            }),
+            ("5' clipping penalty", || {
+                BwaSettings::new().set_clip_scores(-1, 5)
+            }),
+            ("3' clipping penalty", || {
+                BwaSettings::new().set_clip_scores(5, -1)
+            }),
+            ("unpaired penalty", || BwaSettings::new().set_unpaired(-1)),
+            ("minimum output score", || {
+                BwaSettings::new().set_min_output_score(-1)
+            }),
+            ("band width", || BwaSettings::new().set_band_width(0)),
+            ("max insert size", || BwaSettings::new().set_max_insert(0)),
+            ("zdrop", || BwaSettings::new().set_zdrop(0)),
+            ("minimum seed length", || {
+                BwaSettings::new().set_min_seed_len(0)
+            }),
+            ("split factor", || BwaSettings::new().set_split_factor(-1.0)),
+            ("drop ratio", || BwaSettings::new().set_drop_ratio(1.5)),
+            ("max XA hits", || {
+                BwaSettings::new().set_max_xa_hits(-1, 200)
+            }),
+            ("max XA hits for ALT contigs", || {
+                BwaSettings::new().set_max_xa_hits(5, -1)
+            }),
+            ("XA drop ratio", || {
+                BwaSettings::new().set_xa_drop_ratio(-0.1)
+            }),
+            ("split width", || BwaSettings::new().set_split_width(0)),
+            ("max occurrences", || BwaSettings::new().set_max_occ(0)),
+            ("max chain gap", || BwaSettings::new().set_max_chain_gap(0)),
+            ("min chain weight", || {
+                BwaSettings::new().set_min_chain_weight(-1)
+            }),
+            ("max chain extend", || {
+                BwaSettings::new().set_max_chain_extend(0)
+            }),
+            ("max mate rescue rounds", || {
+                BwaSettings::new().set_max_mate_sw(-1)
+            }),
+            ("number of threads", || {
+                BwaSettings::new().set_num_threads(0)
+            }),
+            ("chunk size", || BwaSettings::new().set_chunk_size(0)),
+        ];
+
+        for &(param, set) in invalid {
+            let payload = std::panic::catch_unwind(set).err().unwrap();
+            let msg = payload.downcast_ref::<String>().unwrap();
+            assert!(
+                msg.starts_with(&format!("invalid BWA setting: {} must be", param)),
+                "{}",
+                msg
+            );
+        }
+    }
+
+    #[test]
+    fn gap_penalties() {
+        let settings = BwaSettings::new().set_gap_penalties(10, 2, 6, 1);
+        let p = settings.params();
+        assert_eq!((p.o_del, p.e_del, p.o_ins, p.e_ins), (10, 2, 6, 1));
+        assert!(settings.command_line().contains(" -O 10,6 -E 2,1 "));
+        assert!(BwaSettingsBuilder::new()
+            .gap_penalties(6, 1, 6, 0)
+            .build()
+            .is_err());
+
+        // a read with a 2bp deletion is only affected by the deletion penalties
+        let chr = chr_seq();
+        let seq = [&chr[10000..10050], &chr[10052..10102]].concat();
+        let score = |settings| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            let recs = bwa.align_read(b"deletion", &seq, b"").unwrap();
+            record::alignment_score(&recs[0]).unwrap()
+        };
+        let default = score(BwaSettings::new());
+        assert!(score(BwaSettings::new().set_gap_penalties(10, 1, 6, 1)) < default);
+        assert_eq!(
+            score(BwaSettings::new().set_gap_penalties(6, 1, 10, 1)),
+            default
+        );
+    }
+
+    #[test]
+    fn settings_clone() {
+        let base = BwaSettings::new()
+            .set_scores(2, 8, 12, 2)
+            .set_band_width(50);
+        let variant = base.clone().set_min_seed_len(25).set_no_multi();
+
+        assert_eq!(base.params().min_seed_len, 19);
+        assert_eq!(variant.params().min_seed_len, 25);
+        assert_eq!((variant.params().a, variant.params().w), (2, 50));
+        assert_eq!(base.bwa_settings.mat, variant.bwa_settings.mat);
+        assert!(!base.command_line().ends_with(" -M"));
+        assert!(variant.command_line().ends_with(" -M"));
+    }
+
+    #[test]
+    fn settings_params() {
+        let settings = BwaSettings::new()
+            .set_scores(2, 8, 12, 2)
+            .set_band_width(200)
+            .set_no_multi();
+        let params = settings.params();
+        assert_eq!(
+            (params.a, params.b, params.o_del, params.e_ins),
+            (2, 8, 12, 2)
+        );
+        assert_eq!(params.w, 200);
+        assert_eq!(params.flag & bwa_sys::MEM_F_NO_MULTI as i32, 0x10);
+        assert!(format!("{:?}", settings).contains("w: 200"));
+
+        let restored = BwaSettings::from_params(&params);
+        assert_eq!(restored.params(), params);
+        assert_eq!(params.mat, None);
+        assert_eq!(
+            &restored.bwa_settings.mat[..],
+            &settings.bwa_settings.mat[..]
+        );
+
+        // a matrix that isn't derived from the scores is kept
+        let settings = settings.set_n_penalty(3);
+        let params = settings.params();
+        assert_eq!(params.mat, Some(settings.bwa_settings.mat));
+        let restored = BwaSettings::from_params(&params);
+        assert_eq!(restored.bwa_settings.mat, settings.bwa_settings.mat);
+        assert_eq!(restored.bwa_settings.mat[4], -3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn settings_serde() {
+        extern crate serde_json;
+
+        let settings = BwaSettings::new()
+            .set_min_seed_len(15)
+            .set_output_all()
+            .set_n_penalty(2);
+        let json = serde_json::to_string(&settings).unwrap();
+        let restored: BwaSettings = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.params(), settings.params());
+
+        let align = |settings| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            align_read_with(&bwa, read_split())
+        };
+        assert_eq!(align(restored), align(settings));
+    }
+
+    #[test]
+    fn qual_offset() {
+        let r = read_simple();
+        let mut bwa = load_aligner();
+        let expected = align_read_with(&bwa, r);
+
+        // the same qualities in Phred+64
+        let q1: Vec<u8> = r[2].iter().map(|q| q + 31).collect();
+        let q2: Vec<u8> = r[4].iter().map(|q| q + 31).collect();
+        match bwa.set_qual_offset(32) {
+            Err(AlignError::InvalidQualOffset(32)) => (),
+            res => panic!("expected invalid quality offset error, got {:?}", res),
+        }
+        assert_eq!(align_read_with(&bwa, r), expected);
+
+        bwa.set_qual_offset(64).unwrap();
+        let (r1, r2) = bwa.align_read_pair(r[0], r[1], &q1, r[3], &q2).unwrap();
+        assert_eq!((r1, r2), expected);
+
+        let batch = vec![ReadPair {
+            q1,
+            q2,
+            ..to_read_pair(r)
+        }];
+        assert_eq!(bwa.align_read_pairs(&batch, 1).unwrap()[0], expected);
+
+        // Phred+33 qualities like '2' are invalid as Phred+64
+        match bwa.align_read(r[0], r[1], r[2]) {
+            Err(AlignError::InvalidQual {
+                qual: b'2',
+                offset: 64,
+            }) => (),
+            res => panic!("expected invalid quality error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn lowercase_and_ambiguous_bases() {
+        let bwa = load_aligner();
+        let r = read_simple();
+        let (r1, r2) = align_read_with(&bwa, r);
+
+        let lower1 = r[1].to_ascii_lowercase();
+        let lower2 = r[3].to_ascii_lowercase();
+        let (lr1, lr2) = bwa
+            .align_read_pair(r[0], &lower1, r[2], &lower2, r[4])
+            .unwrap();
+        assert_eq!((lr1, lr2), (r1.clone(), r2.clone()));
+
+        // ambiguity codes align as N, as does N itself
+        let mut amb1 = r[1].to_vec();
+        amb1[50] = b'R';
+        let mut n1 = r[1].to_vec();
+        n1[50] = b'N';
+        let (ar1, _) = bwa.align_read_pair(r[0], &amb1, r[2], r[3], r[4]).unwrap();
+        let (nr1, _) = bwa.align_read_pair(r[0], &n1, r[2], r[3], r[4]).unwrap();
+        assert_eq!(ar1, nr1);
+        assert_eq!(ar1[0].pos(), r1[0].pos());
+        assert_eq!(ar1[0].cigar(), r1[0].cigar());
+    }
+
+    #[test]
+    fn drop_ratio() {
+        let settings = BwaSettings::new().set_split_factor(1.0).set_drop_ratio(0.8);
+        assert_eq!(settings.params().drop_ratio, 0.8);
+        assert!(settings.command_line().contains(" -r 1 -D 0.8 "));
+
+        // the exact copies of a repeat score equally, so all survive any drop ratio
+        let seq = &chr_seq()[15400..15500];
+        let qual = vec![b'I'; seq.len()];
+        let n_recs: Vec<_> = [0.0, 0.5, 1.0]
+            .iter()
+            .map(|&drop_ratio| {
+                let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+                let settings = BwaSettings::new()
+                    .set_output_all()
+                    .set_drop_ratio(drop_ratio);
+                let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+                bwa.align_read(b"repeat", seq, &qual).unwrap().len()
+            })
+            .collect();
+        assert!(n_recs[0] > 1);
+        assert!(n_recs.iter().all(|&n| n == n_recs[0]));
+    }
+
+    #[test]
+    fn score_matrix() {
+        // the default matrix, as filled in by bwa_fill_scmat
+        let mut mat = [-4i8; 25];
+        for i in 0..4 {
+            mat[i * 5 + i] = 1;
+        }
+        for i in 0..5 {
+            mat[i * 5 + 4] = -1;
+            mat[4 * 5 + i] = -1;
+        }
+        assert_eq!(BwaSettings::new().bwa_settings.mat, mat);
+
+        // a read with 4 C->T conversions
+        let mut seq = chr_seq()[10000..10100].to_vec();
+        let converted: Vec<usize> = (20..80)
+            .filter(|&i| seq[i] == b'C')
+            .step_by(3)
+            .take(4)
+            .collect();
+        assert_eq!(converted.len(), 4);
+        for &i in &converted {
+            seq[i] = b'T';
+        }
+        let qual = vec![b'I'; seq.len()];
+
+        let score = |settings: BwaSettings| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            let recs = bwa.align_read(b"bisulfite", &seq, &qual).unwrap();
+            assert_eq!(recs[0].pos(), 10000);
+            record::alignment_score(&recs[0]).unwrap()
+        };
+
+        // reference C to read T scores as a match
+        let mut bisulfite = mat;
+        let (c, t) = (1, 3);
+        bisulfite[c * 5 + t] = 1;
+        assert_eq!(score(BwaSettings::new()), 100 - 4 * 5);
+        assert_eq!(score(BwaSettings::new().set_score_matrix(bisulfite)), 100);
+
+        // but the matrix isn't symmetric
+        let mut reverse = mat;
+        reverse[3 * 5 + 1] = 1;
+        assert_eq!(
+            score(BwaSettings::new().set_score_matrix(reverse)),
+            100 - 4 * 5
+        );
+    }
+
+    #[test]
+    fn n_penalty() {
+        let mat = BwaSettings::new().set_n_penalty(3).bwa_settings.mat;
+        assert_eq!(&mat[20..25], &[-3; 5]);
+        assert!((0..5).all(|i| mat[i * 5 + 4] == -3));
+        assert_eq!(&mat[0..4], &[1, -4, -4, -4]);
+        assert_eq!(
+            BwaSettings::new().set_n_penalty(1).bwa_settings.mat,
+            BwaSettings::new().bwa_settings.mat
+        );
+
+        // a 25 bp seed, followed by 35 bases of which every other one is N. At the default
+        // penalty, the Ns cancel out the matches between them, leaving the read below the
+        // minimum output score.
+        let mut seq = chr_seq()[30000..30060].to_vec();
+        for i in (25..60).step_by(2) {
+            seq[i] = b'N';
+        }
+        let qual = vec![b'I'; seq.len()];
+
+        let align = |settings: BwaSettings| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            bwa.align_read(b"ns", &seq, &qual).unwrap()
+        };
+
+        assert!(align(BwaSettings::new())[0].is_unmapped());
+        let recs = align(BwaSettings::new().set_n_penalty(0));
+        assert!(!recs[0].is_unmapped());
+        assert_eq!(recs[0].pos(), 30000);
+        assert_eq!(record::alignment_score(&recs[0]).unwrap(), 42);
+    }
+
+    #[test]
+    fn max_occ() {
+        let settings = BwaSettings::new().set_max_occ(1000);
+        assert_eq!(settings.params().max_occ, 1000);
+        assert!(settings.command_line().contains(" -c 1000 "));
+        assert!(BwaSettingsBuilder::new().max_occ(0).build().is_err());
+
+        // the read is in a 3-copy exact repeat, so its seeds each occur 3 times
+        let seq = &chr_seq()[15400..15500];
+        let qual = vec![b'I'; seq.len()];
+        let n_recs = |max_occ| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let settings = BwaSettings::new().set_output_all().set_max_occ(max_occ);
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            bwa.align_read(b"repeat", seq, &qual).unwrap().len()
+        };
+        assert_eq!(n_recs(500), 3);
+        // seeds occurring more than max_occ times are sampled rather than dropped, so the
+        // read still maps, but only to the sampled copy
+        assert!(n_recs(1) < n_recs(500));
+    }
+
+    #[test]
+    fn chain_settings() {
+        let settings = BwaSettings::new()
+            .set_max_chain_gap(5000)
+            .set_min_chain_weight(30)
+            .set_max_chain_extend(10);
+        let p = settings.params();
+        assert_eq!(
+            (p.max_chain_gap, p.min_chain_weight, p.max_chain_extend),
+            (5000, 30, 10)
+        );
+        let cl = settings.command_line();
+        assert!(cl.contains(" -G 5000") && cl.ends_with(" -W 30"));
+        assert!(BwaSettingsBuilder::new().max_chain_gap(0).build().is_err());
+        assert!(BwaSettingsBuilder::new()
+            .min_chain_weight(-1)
+            .build()
+            .is_err());
+
+        // a 100bp read can't have chains covering more than 100 bases
+        let seq = &chr_seq()[10000..10100];
+        let qual = vec![b'I'; seq.len()];
+        let align = |settings| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            bwa.align_read(b"read", seq, &qual).unwrap()
+        };
+        assert!(!align(BwaSettings::new().set_min_chain_weight(50))[0].is_unmapped());
+        assert!(align(BwaSettings::new().set_min_chain_weight(200))[0].is_unmapped());
+    }
+
+    #[test]
+    fn max_xa_hits() {
+        let settings = BwaSettings::new().set_max_xa_hits(10, 100);
+        assert!(settings.command_line().contains(" -h 10,100"));
+
+        // the read is in a 3-copy exact repeat, so has 2 alternative hits
+        let seq = &chr_seq()[15400..15500];
+        let qual = vec![b'I'; seq.len()];
+        let xa_hits = |settings: BwaSettings| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            let recs = bwa.align_read(b"repeat", seq, &qual).unwrap();
+            match recs[0].aux(b"XA") {
+                Ok(Aux::String(xa)) => xa.split(';').filter(|h| !h.is_empty()).count(),
+                _ => 0,
+            }
+        };
+
+        assert_eq!(xa_hits(BwaSettings::new().set_max_xa_hits(1, 1)), 0);
+        assert_eq!(xa_hits(BwaSettings::new().set_max_xa_hits(5, 200)), 2);
+        assert_eq!(
+            xa_hits(
+                BwaSettings::new()
+                    .set_max_xa_hits(5, 200)
+                    .set_xa_drop_ratio(1.0)
+            ),
+            2
+        );
+        assert!(BwaSettingsBuilder::new()
+            .xa_drop_ratio(1.5)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn align_with_callback() {
+        let bwa = load_aligner();
+        for r in &[read_simple(), read_split()] {
+            let (recs1, recs2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+
+            let mut recs = Vec::new();
+            bwa.align_read_pair_with(r[0], r[1], r[2], r[3], r[4], |rec| recs.push(rec))
+                .unwrap();
+            assert_eq!(recs, [recs1, recs2].concat());
+            assert!(recs[0].is_first_in_template());
+            assert!(recs.last().unwrap().is_last_in_template());
+        }
+
+        let mut n = 0;
+        let res = bwa.align_read_pair_with(b"read\0", b"ACGT", b"", b"ACGT", b"", |_| n += 1);
+        assert!(res.is_err());
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn align_into_scratch() {
+        let bwa = load_aligner();
+        let mut scratch = AlignScratch::new();
+        let (mut out1, mut out2) = (Vec::new(), Vec::new());
+
+        for &r in &[read_split(), read_simple(), read_split()] {
+            bwa.align_read_pair_into(&mut scratch, &to_read_pair(r), &mut out1, &mut out2)
+                .unwrap();
+            let (r1, r2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+            assert_eq!((&out1, &out2), (&r1, &r2));
+        }
+
+        let mut pair = to_read_pair(read_simple());
+        pair.name = b"bad\0name".to_vec();
+        let res = bwa.align_read_pair_into(&mut scratch, &pair, &mut out1, &mut out2);
+        match res {
+            Err(AlignError::InvalidName(_)) => (),
+            res => panic!("expected invalid name error, got {:?}", res),
+        }
+    }
+
+    // Exercises the ownership of the buffers passed to BWA across many calls. Run under a
+    // leak checker to catch leaks or double-frees, e.g.
+    // `RUSTFLAGS=-Zsanitizer=address cargo +nightly test repeated_alignment`
+    #[test]
+    fn repeated_alignment() {
+        let seq = chr_seq();
+        let r1 = &seq[10000..10100];
+        let r2 = revcomp(&seq[10200..10300]);
+        let q = vec![b'I'; 100];
+
+        let bwa = load_aligner();
+        for i in 0..1000 {
+            let name = format!("pair{}", i).into_bytes();
+            let (recs1, recs2) = bwa.align_read_pair(&name, r1, &q, &r2, &q).unwrap();
+            assert_eq!(recs1[0].qname(), &name[..]);
+            assert_eq!(recs2[0].qname(), &name[..]);
+            assert_eq!(bwa.align_read(&name, r1, &q).unwrap()[0].qname(), &name[..]);
+        }
+
+        let batch: Vec<_> = (0..100)
+            .map(|i| ReadPair {
+                name: format!("pair{}", i).into_bytes(),
+                r1: r1.to_vec(),
+                q1: q.clone(),
+                r2: r2.clone(),
+                q2: q.clone(),
+            })
+            .collect();
+        for _ in 0..10 {
+            let pairs = bwa.align_read_pairs(&batch, 2).unwrap();
+            for (pair, (recs1, recs2)) in batch.iter().zip(&pairs) {
+                assert_eq!(recs1[0].qname(), &pair.name[..]);
+                assert_eq!(recs2[0].qname(), &pair.name[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn align_with_comment() {
+        let bwa = load_aligner();
+        let r = read_split();
+        let comment: &[u8] = b"BX:Z:AAAA-1\tQT:Z:FFFF";
+
+        let (recs1, recs2) = bwa
+            .align_read_pair_with_comment(r[0], r[1], r[2], r[3], r[4], Some(comment))
+            .unwrap();
+        // including the supplementary record of read 1
+        assert!(recs1.len() > 1);
+        for rec in recs1.iter().chain(&recs2) {
+            assert_eq!(rec.aux(b"BX").unwrap(), Aux::String("AAAA-1"));
+            assert_eq!(rec.aux(b"QT").unwrap(), Aux::String("FFFF"));
+        }
+
+        let (recs1, recs2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        let (none1, none2) = bwa
+            .align_read_pair_with_comment(r[0], r[1], r[2], r[3], r[4], None)
+            .unwrap();
+        assert_eq!((recs1, recs2), (none1, none2));
+
+        // array tags, such as base modification probabilities, are kept too
+        let (recs1, _) = bwa
+            .align_read_pair_with_comment(r[0], r[1], r[2], r[3], r[4], Some(b"ML:B:C,200,10"))
+            .unwrap();
+        match recs1[0].aux(b"ML") {
+            Ok(Aux::ArrayU8(a)) => assert_eq!(a.iter().collect::<Vec<_>>(), [200, 10]),
+            aux => panic!("expected u8 array, got {:?}", aux),
+        }
+
+        // the comment's read group replaces the aligner's
+        let rg_bwa = load_aligner().with_read_group(ReadGroup::new("rg1"));
+        let (recs1, recs2) = rg_bwa
+            .align_read_pair_with_comment(r[0], r[1], r[2], r[3], r[4], Some(b"RG:Z:x"))
+            .unwrap();
+        for rec in recs1.iter().chain(&recs2) {
+            assert_eq!(rec.aux(b"RG").unwrap(), Aux::String("x"));
+        }
+        let (recs1, _) = rg_bwa
+            .align_read_pair(r[0], r[1], r[2], r[3], r[4])
+            .unwrap();
+        assert_eq!(recs1[0].aux(b"RG").unwrap(), Aux::String("rg1"));
+
+        let res = bwa.align_read_pair_with_comment(r[0], r[1], r[2], r[3], r[4], Some(b"BX:Z:A\0"));
+        assert!(res.is_err());
+        let res =
+            bwa.align_read_pair_with_comment(r[0], r[1], r[2], r[3], r[4], Some(b"not a tag"));
+        match res {
+            Err(AlignError::SamParse { line, .. }) => assert!(line.ends_with("not a tag")),
+            res => panic!("expected SAM parse error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn read_ids() {
+        // a pair from within a 3-copy exact repeat
+        let frag = &chr_seq()[15400..15500];
+        let r1 = &frag[..60];
+        let r2 = revcomp(&frag[40..]);
+        let q = [b'I'; 60];
+
+        let bwa = load_aligner();
+        let positions: HashSet<_> = (0..20)
+            .map(|id| {
+                let (recs1, _) = bwa
+                    .align_read_pair_with_id(id, b"pair", r1, &q, &r2, &q)
+                    .unwrap();
+                recs1[0].pos()
+            })
+            .collect();
+        assert!(positions.len() > 1);
+
+        // differently-named reads get different ids, but the same read always aligns
+        // the same way
+        assert_ne!(read_id(b"pair1"), read_id(b"pair2"));
+        let positions: HashSet<_> = (0..20)
+            .map(|i| {
+                let name = format!("pair{}", i).into_bytes();
+                let (recs1, _) = bwa.align_read_pair(&name, r1, &q, &r2, &q).unwrap();
+                let (again, _) = bwa.align_read_pair(&name, r1, &q, &r2, &q).unwrap();
+                assert_eq!(recs1, again);
+                recs1[0].pos()
+            })
+            .collect();
+        assert!(positions.len() > 1);
+    }
+
+    #[test]
+    fn deterministic_batches() {
+        // pairs from within a 3-copy exact repeat, so every pair relies on tie-breaking
+        let frag = &chr_seq()[15400..15500];
+        let r1 = frag[..60].to_vec();
+        let r2 = revcomp(&frag[40..]);
+        let q = vec![b'I'; 60];
+        let batch: Vec<_> = (0..1000)
+            .map(|i| ReadPair {
+                name: format!("pair{}", i).into_bytes(),
+                r1: r1.clone(),
+                q1: q.clone(),
+                r2: r2.clone(),
+                q2: q.clone(),
+            })
+            .collect();
+
+        // the choice between equal hits depends only on the read name, not on the
+        // thread count or on where the pair falls in the batch
+        let bwa = load_aligner();
+        let expected = bwa.align_read_pairs(&batch, 1).unwrap();
+        assert_eq!(bwa.align_read_pairs(&batch, 4).unwrap(), expected);
+
+        let reversed: Vec<_> = batch.iter().rev().cloned().collect();
+        let mut aligned = bwa.align_read_pairs(&reversed, 3).unwrap();
+        aligned.reverse();
+        assert_eq!(aligned, expected);
+
+        let single: Vec<_> = batch
+            .iter()
+            .map(|p| {
+                bwa.align_read_pair(&p.name, &p.r1, &p.q1, &p.r2, &p.q2)
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(single, expected);
+
+        let positions: HashSet<_> = expected.iter().map(|p| p.0[0].pos()).collect();
+        assert!(positions.len() > 1);
+    }
+
+    #[test]
+    fn deterministic_single_end_batches() {
+        let read = chr_seq()[15410..15490].to_vec();
+        let q = vec![b'I'; read.len()];
+        let batch: Vec<_> = (0..500)
+            .map(|i| (format!("read{}", i).into_bytes(), read.clone(), q.clone()))
+            .collect();
+
+        let bwa = load_aligner();
+        let expected = bwa.align_reads(&batch, 1).unwrap();
+        assert_eq!(bwa.align_reads(&batch, 4).unwrap(), expected);
+        assert_eq!(bwa.align_reads(&batch[250..], 2).unwrap(), &expected[250..]);
+
+        let single: Vec<_> = batch
+            .iter()
+            .map(|r| bwa.align_read(&r.0, &r.1, &r.2).unwrap())
+            .collect();
+        assert_eq!(single, expected);
+
+        let positions: HashSet<_> = expected.iter().map(|r| r[0].pos()).collect();
+        assert!(positions.len() > 1);
+    }
+
+    #[test]
+    fn query_preserved() {
+        let seq = chr_seq();
+        let r1 = seq[10000..10100].to_ascii_lowercase();
+        let r2 = revcomp(&seq[10200..10300]);
+        let q = [b'I'; 100];
+
+        let bwa = load_aligner();
+        let (read1, read2) = bwa
+            .align_read_pair_with_query(b"pair", &r1, &q, &r2, &q)
+            .unwrap();
+
+        assert_eq!(read1.query, r1);
+        assert_eq!(read2.query, r2);
+        assert!(!read1.is_reverse(0));
+        assert!(read2.is_reverse(0));
+
+        // the records hold the uppercased read, reverse-complemented for the reverse strand
+        assert_eq!(read1.records[0].seq().as_bytes(), r1.to_ascii_uppercase());
+        assert_eq!(read2.records[0].seq().as_bytes(), revcomp(&r2));
+    }
+
+    #[test]
+    fn presets() {
+        let s = BwaSettings::preset(Preset::PacBio).bwa_settings;
+        assert_eq!((s.o_del, s.e_del, s.o_ins, s.e_ins), (1, 1, 1, 1));
+        assert_eq!((s.a, s.b), (1, 1));
+        assert_eq!((s.pen_clip5, s.pen_clip3), (0, 0));
+        assert_eq!(s.split_factor, 10.0);
+        assert_eq!(s.min_chain_weight, 40);
+        assert_eq!(s.min_seed_len, 17);
+        // the scoring matrix is updated for the new mismatch penalty
+        assert_eq!(s.mat[1], -1);
+
+        let s = BwaSettings::preset(Preset::Ont2d).bwa_settings;
+        assert_eq!((s.min_chain_weight, s.min_seed_len), (20, 14));
+
+        let s = BwaSettings::preset(Preset::IntraCtg).bwa_settings;
+        assert_eq!((s.o_del, s.o_ins, s.b), (16, 16, 9));
+        assert_eq!((s.pen_clip5, s.pen_clip3), (5, 5));
+        assert_eq!(s.min_seed_len, 19);
+
+        let cl = BwaSettings::preset(Preset::PacBio).command_line();
+        assert!(cl.contains(" -k 17 ") && cl.contains(" -B 1 ") && cl.ends_with(" -W 40"));
+
+        // reads still align with the preset scores
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let bwa = BwaAligner::new(
+            reference,
+            BwaSettings::preset(Preset::PacBio),
+            PairedEndStats::default(),
+        );
+        let recs = bwa.align_read(b"read", read_split()[1], b"").unwrap();
+        assert!(!recs[0].is_unmapped());
+    }
+
+    #[test]
+    fn test_load_aligner() {
+        let _ = load_aligner();
     }
 
     fn read_simple() -> [&'static [u8]; 5] {
@@ -355,42 +4715,2024 @@ mod tests {
         let r1  : &[u8] = b"GATGGCTGCGCAAGGGTTCTTACTGATCGCCACGTTTTTACTGGTGTTAATGGTGCTGGCGCGTCCTTTAGGCAGCGGGCTGGCGCGGCTGATTAATGACATTCCTCTTCCCGGTACAACGGGCGTTGAGCGCGAACTTTTTCGCGCACT";
         let q1  : &[u8] = b"222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222";
 
-        let r2  : &[u8] = b"TGCTGCGTAGCAGATCGACCCAGGCATTCCCTAGCGTGCTCATGCTCTGGCTGGTAAACGCACGGATGAGGGCAAAAATCACCGCAATCCCGCTGGCGGCAGAAAGAAAGTTTTGCACCGTTAAGCCCGCCATCTGGCTGAAATAGCTCA";
-        let q2  : &[u8] = b"222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222";
+        let r2  : &[u8] = b"TGCTGCGTAGCAGATCGACCCAGGCATTCCCTAGCGTGCTCATGCTCTGGCTGGTAAACGCACGGATGAGGGCAAAAATCACCGCAATCCCGCTGGCGGCAGAAAGAAAGTTTTGCACCGTTAAGCCCGCCATCTGGCTGAAATAGCTCA";
+        let q2  : &[u8] = b"222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222";
+
+        [name, r1, q1, r2, q2]
+    }
+
+    fn read_split() -> [&'static [u8]; 5] {
+        let name = b"@chr_1561275_1561756_1:0:0_2:0:0_5c/1";
+        let r1 = b"GCATCGATAAGCAGGTCAAATTCTCCCGTCATTATCACCTCTGCTACTTAAATTTCCCGCTTTATAAGCCGATTACGGCCTGGCATTACCCTATCCATAATTTAGGTGGGATGCCCGGTGCGTGGTTGGCAGATCCGCTGTTCTTTATTT";
+        let q1 = b"222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222";
+
+        let r2 = b"TCATCGACCCAGGTATCATCGCGACGGGTACGATTACTGGCGAAGGTGAGAATGTTTAAAATCCAGCCGCCGAGTTTTTCAGCAATGGTCACCCATGACCAACCGGTGAACAACGTGAGGGCCGCTGCCCAAACGCATAGCAGCGCAATA";
+        let q2 = b"222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222";
+
+        [name, r1, q1, r2, q2]
+    }
+
+    fn to_read_pair(r: [&[u8]; 5]) -> ReadPair {
+        ReadPair {
+            name: r[0].to_vec(),
+            r1: r[1].to_vec(),
+            q1: r[2].to_vec(),
+            r2: r[3].to_vec(),
+            q2: r[4].to_vec(),
+        }
+    }
+
+    #[test]
+    fn alignment_scores() {
+        let bwa = load_aligner();
+        let r = read_simple();
+
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        let score = record::alignment_score(&recs[0]).unwrap();
+        // one mismatch: -1 match, -4 mismatch penalty
+        assert_eq!(score, r[1].len() as i32 - 5);
+
+        let regions = bwa.align_read_regions(r[1]);
+        assert!(!regions.is_empty());
+        assert_eq!(regions[0].score, score);
+        assert_eq!(regions[0].tid, recs[0].tid());
+        assert_eq!(regions[0].ref_start, recs[0].pos());
+        assert!(regions[0].is_reverse);
+        assert!(!regions[0].is_secondary);
+        assert_eq!(regions[0].query_start, 0);
+        assert_eq!(regions[0].query_end, r[1].len() as i32);
+        assert_eq!(regions[0].mapq_estimate, recs[0].mapq());
+
+        // a read in a 3-copy exact repeat has no unique placement
+        let seq = &chr_seq()[15400..15500];
+        let regions = bwa.align_read_regions(seq);
+        assert_eq!(regions.len(), 3);
+        assert!(regions.iter().all(|reg| reg.mapq_estimate == 0));
+        assert_eq!(regions.iter().filter(|reg| !reg.is_secondary).count(), 1);
+    }
+
+    #[test]
+    fn alignment_spans() {
+        let bwa = load_aligner();
+        let chr = chr_seq();
+
+        let recs = bwa.align_read(b"read", &chr[10000..10100], b"").unwrap();
+        assert_eq!(record::alignment_span(&recs[0]), Some((10001, 10100)));
+
+        // a 2bp deletion extends the span on the reference
+        let seq = [&chr[10000..10050], &chr[10052..10102]].concat();
+        let recs = bwa.align_read(b"deletion", &seq, b"").unwrap();
+        assert_eq!(record::alignment_span(&recs[0]), Some((10001, 10102)));
+
+        let recs = bwa.align_read(b"unmapped", &[b'N'; 100], b"").unwrap();
+        assert_eq!(record::alignment_span(&recs[0]), None);
+    }
+
+    #[test]
+    fn template_lengths() {
+        let bwa = load_aligner();
+        let fr = bwa.pe_stats.insert_size(PairOrientation::FR).unwrap();
+
+        let r = read_simple();
+        let (recs1, recs2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        let (rec1, rec2) = (&recs1[0], &recs2[0]);
+        assert!(rec1.is_proper_pair() && rec2.is_proper_pair());
+
+        let tlen1 = record::template_length(rec1).unwrap();
+        let tlen2 = record::template_length(rec2).unwrap();
+        assert_eq!(tlen1, -tlen2);
+        // the leftmost read has the positive length
+        assert_eq!(tlen1 > 0, rec1.pos() < rec2.pos());
+        assert!(tlen1.abs() >= fr.low as i64 && tlen1.abs() <= fr.high as i64);
+
+        // read 2 doesn't align, so there's no template length
+        let junk = vec![b'N'; r[3].len()];
+        let (recs1, recs2) = bwa.align_read_pair(r[0], r[1], r[2], &junk, r[4]).unwrap();
+        assert!(!recs1[0].is_proper_pair());
+        assert_eq!(record::template_length(&recs1[0]), None);
+        assert_eq!(record::template_length(&recs2[0]), None);
+
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        assert_eq!(record::template_length(&recs[0]), None);
+    }
+
+    #[test]
+    fn mismatch_tags() {
+        let bwa = load_aligner();
+        // one mismatch against the reference C at offset 30 of a unique region
+        let mut seq = chr_seq()[10000..10100].to_vec();
+        assert_eq!(seq[30], b'C');
+        seq[30] = b'A';
+        let qual = vec![b'I'; seq.len()];
+
+        let recs = bwa.align_read(b"mismatch", &seq, &qual).unwrap();
+        assert_eq!(recs[0].cigar().to_string(), "100M");
+        assert_eq!(record::mismatch_string(&recs[0]), Some("30C69"));
+        assert_eq!(record::edit_distance(&recs[0]), Some(1));
+        // 99 matches at 1 less one mismatch at 4
+        assert_eq!(record::alignment_score(&recs[0]), Some(95));
+        assert!(record::suboptimal_score(&recs[0]).unwrap() < 95);
+
+        // every primary record of a pair has the tags
+        for r in &[read_simple(), read_split()] {
+            let (recs1, recs2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+            for rec in [&recs1[0], &recs2[0]].iter() {
+                assert!(record::mismatch_string(rec).is_some());
+                assert!(record::edit_distance(rec).is_some());
+                assert!(record::alignment_score(rec).is_some());
+                assert!(record::suboptimal_score(rec).is_some());
+            }
+        }
+
+        let n = vec![b'N'; 100];
+        let recs = bwa.align_read(b"unmapped", &n, b"").unwrap();
+        assert_eq!(record::mismatch_string(&recs[0]), None);
+        assert_eq!(record::edit_distance(&recs[0]), None);
+    }
+
+    #[test]
+    fn owned_alignment() {
+        let bwa = load_aligner();
+        let r = read_split();
+        let (r1, _) = align_read_with(&bwa, r);
+
+        // the owned copies can be sent to another thread
+        let owned: Vec<OwnedAlignment> = r1.iter().map(OwnedAlignment::from).collect();
+        let owned = std::thread::spawn(move || owned).join().unwrap();
+
+        for (rec, aln) in r1.iter().zip(&owned) {
+            assert_eq!(aln.qname, rec.qname());
+            assert_eq!(aln.flags, rec.flags());
+            assert_eq!(
+                (aln.tid, aln.pos, aln.mapq),
+                (rec.tid(), rec.pos(), rec.mapq())
+            );
+            assert_eq!(aln.cigar, rec.cigar().iter().cloned().collect::<Vec<_>>());
+            assert_eq!((aln.mtid, aln.mpos), (rec.mtid(), rec.mpos()));
+            assert_eq!(aln.insert_size, rec.insert_size());
+            assert_eq!(aln.seq, rec.seq().as_bytes());
+            assert_eq!(aln.qual, rec.qual());
+            assert_eq!(
+                aln.aux(b"AS"),
+                Some(&AuxValue::Int(record::alignment_score(rec).unwrap() as i64))
+            );
+            assert!(aln.aux(b"MD").is_some());
+        }
+        assert_eq!(owned[0].seq.len(), r[1].len());
+        match owned[0].aux(b"SA") {
+            Some(AuxValue::String(sa)) => assert!(sa.starts_with("chr,")),
+            aux => panic!("expected SA tag, got {:?}", aux),
+        }
+        assert_eq!(owned[0].aux(b"ZZ"), None);
+    }
+
+    #[test]
+    fn align_with_tags() {
+        let bwa = load_aligner();
+        let r = read_split();
+        let r1_tags = [
+            (*b"MM", AuxValue::String("C+m,0,2;".to_string())),
+            (*b"ML", AuxValue::IntArray(vec![200, 10])),
+        ];
+        let r2_tags = [(*b"XT", AuxValue::Char(b'U'))];
+
+        let (recs1, recs2) = bwa
+            .align_read_pair_with_tags(&to_read_pair(r), &r1_tags, &r2_tags)
+            .unwrap();
+        let expected = align_read_with(&bwa, r);
+        assert_eq!(recs1.len(), expected.0.len());
+        assert_eq!(recs2.len(), expected.1.len());
+
+        // every record of each read has its tags, after those added by BWA
+        for (recs, tags) in [(&recs1, &r1_tags[..]), (&recs2, &r2_tags[..])] {
+            for rec in recs {
+                let aln = OwnedAlignment::from(rec);
+                assert_eq!(&aln.aux[aln.aux.len() - tags.len()..], tags);
+                assert!(aln.aux(b"NM").is_some());
+            }
+        }
+        assert_eq!(OwnedAlignment::from(&recs2[0]).aux(b"MM"), None);
+    }
+
+    #[test]
+    fn alignment_score_unmapped() {
+        let r = read_simple();
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_min_output_score(1000);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        assert_eq!(record::alignment_score(&recs[0]), None);
+    }
+
+    #[test]
+    fn min_output_score_align() {
+        let r = read_simple();
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_min_output_score(0);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        assert!(!recs[0].is_unmapped());
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_min_output_score(1000);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        assert_eq!(recs.len(), 1);
+        assert!(recs[0].is_unmapped());
+        assert_eq!(recs[0].flags() & 0x4, 0x4);
+    }
+
+    #[test]
+    fn raw_settings() {
+        let r = read_simple();
+        let align = |settings: BwaSettings| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            bwa.align_read(r[0], r[1], r[2]).unwrap()
+        };
+
+        for &min_score in &[0, 1000] {
+            let mut settings = BwaSettings::new();
+            settings.modify_raw(|opt| opt.T = min_score);
+            assert_eq!(settings.as_raw().T, min_score);
+
+            let expected = BwaSettings::new().set_min_output_score(min_score);
+            assert_eq!(settings.params(), expected.params());
+            assert_eq!(align(settings), align(expected));
+        }
+
+        let mut settings = BwaSettings::new().set_band_width(50);
+        settings.modify_raw(|opt| opt.flag |= MEM_F_SOFTCLIP as i32);
+        assert_eq!(
+            settings.params(),
+            BwaSettings::new()
+                .set_band_width(50)
+                .set_softclip_supplementary()
+                .params()
+        );
+
+        let from_raw = BwaSettings::from_raw(*settings.as_raw());
+        assert_eq!(from_raw.params(), settings.params());
+        assert_eq!(from_raw.as_raw().mat, settings.as_raw().mat);
+    }
+
+    #[test]
+    fn band_width_align() {
+        // 150bp read from chr:3000000 with a 40bp deletion after the first 60bp
+        let seq = b"GCTACATCAGTCAGCGATGAATCTGACCCTGATAAAAGGCCATATCGTGCTGGTTGAACGGACGCTTTCTACCACCCTGAACGCGGCGGGCAGCTCTCTGCTGAAAGCTCCATCAAAACCACCACTAACCCACCGGCGTTTGGCTGTACC";
+        let qual = vec![b'I'; seq.len()];
+        let has_deletion =
+            |recs: &[Record]| recs.len() == 1 && recs[0].cigar().to_string().contains("40D");
+
+        // a narrow band can't span the deletion, so the read is clipped or split
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_band_width(10);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let recs = bwa.align_read(b"deletion", seq, &qual).unwrap();
+        assert!(!has_deletion(&recs));
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_band_width(200).set_zdrop(200);
+        assert!(settings.command_line().contains(" -w 200 -d 200 "));
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let recs = bwa.align_read(b"deletion", seq, &qual).unwrap();
+        assert!(has_deletion(&recs));
+        assert_eq!(recs[0].tid(), 1);
+        assert_eq!(recs[0].pos(), 3000000);
+    }
+
+    #[test]
+    fn min_output_score_pair_align() {
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_min_output_score(1000);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+
+        let (r1, r2) = align_read_with(&bwa, read_simple());
+        assert_eq!(r1.len(), 1);
+        assert_eq!(r2.len(), 1);
+        for rec in r1.iter().chain(&r2) {
+            assert!(rec.is_unmapped());
+            assert_eq!(rec.cigar_len(), 0);
+        }
+    }
+
+    #[test]
+    fn output_all_align() {
+        // 100bp read from a 3-copy exact repeat in chr
+        let seq = b"ACTTAAGGGTTGTGGTATTACGCCTGATATGATTTAACGTGCCGATGAATTACTCTCACGATAACTGGTCAGCAATTCTGGCCCATATTGGTAAGCCCGA";
+        let qual = vec![b'I'; seq.len()];
+
+        let bwa = load_aligner();
+        let recs = bwa.align_read(b"repeat", seq, &qual).unwrap();
+        assert_eq!(recs.len(), 1);
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_output_all();
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let recs = bwa.align_read(b"repeat", seq, &qual).unwrap();
+        assert!(recs.len() > 1);
+        assert!(!recs[0].is_secondary());
+        for rec in &recs[1..] {
+            assert!(rec.is_secondary());
+            assert_eq!(rec.tid(), 1);
+        }
+    }
+
+    #[test]
+    fn short_seed_align() {
+        // 60bp read from chr:2000000 with mismatches at offsets 16, 33 & 50, so its longest
+        // exact match to the reference is 16bp -- too short for the default seed length of 19.
+        let seq = b"GGCGTAAACGCCTTATGCGGCCTACAAAAATGTTCAAATTCAATAAATTGGAATTCAACT";
+        let qual = vec![b'I'; seq.len()];
+
+        let bwa = load_aligner();
+        let recs = bwa.align_read(b"short_seed", seq, &qual).unwrap();
+        assert!(recs[0].is_unmapped());
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_min_seed_len(14);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let recs = bwa.align_read(b"short_seed", seq, &qual).unwrap();
+        assert!(!recs[0].is_unmapped());
+        assert_eq!(recs[0].tid(), 1);
+        assert_eq!(recs[0].pos(), 2000000);
+    }
+
+    #[test]
+    fn open_bns_only() {
+        let reference =
+            BwaReference::open_with_flags("tests/test_ref.fa", IdxLoadFlags::BNS).unwrap();
+        assert_eq!(reference.loaded(), IdxLoadFlags::BNS);
+
+        let header = reference.create_bam_header();
+        let header_text = String::from_utf8(header.to_bytes()).unwrap();
+        assert!(header_text.contains("@SQ\tSN:PhiX\tLN:5386"));
+
+        let res = BwaAligner::try_new(reference, BwaSettings::new(), PairedEndStats::default());
+        assert!(res.is_err());
+
+        let res = BwaReference::open_with_flags("tests/test_ref.fa", IdxLoadFlags::BWT);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn long_seed_align() {
+        // read_simple r1's longest exact match to the reference is 134bp, so no
+        // seed is found if the minimum seed length is longer than that
+        let r = read_simple();
+        let bwa = load_aligner();
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        assert!(!recs[0].is_unmapped());
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_min_seed_len(140);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        assert_eq!(recs.len(), 1);
+        assert!(recs[0].is_unmapped());
+    }
+
+    #[test]
+    fn build_index() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-build-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Index just the PhiX contig of the test reference
+        let fasta = std::fs::read_to_string("tests/test_ref.fa").unwrap();
+        let phix: String = fasta
+            .lines()
+            .skip(1)
+            .take_while(|l| !l.starts_with('>'))
+            .collect();
+        let fasta_path = dir.join("phix.fa");
+        std::fs::write(&fasta_path, format!(">PhiX\n{}\n", phix)).unwrap();
+
+        BwaReference::build_index(&fasta_path, IndexAlgorithm::Is).unwrap();
+
+        let bwa = BwaAligner::from_path(&fasta_path).unwrap();
+        let seq = &phix.as_bytes()[1000..1100];
+        let qual = vec![b'I'; seq.len()];
+        let recs = bwa.align_read(b"phix_read", seq, &qual).unwrap();
+        assert_eq!(recs[0].tid(), 0);
+        assert_eq!(recs[0].pos(), 1000);
+        assert!(!recs[0].is_reverse());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reference_from_sequences() {
+        let chr = chr_seq();
+        let reference = BwaReference::from_sequences(&[
+            ("amp1", &chr[20000..20600]),
+            ("amp2", &chr[40000..40600]),
+        ])
+        .unwrap();
+        assert_eq!(reference.contig_names(), &["amp1", "amp2"]);
+        assert_eq!(reference.contig_lengths(), &[600, 600]);
+        assert_eq!(reference.contig_tid("amp2"), Some(1));
+        assert_eq!(reference.contig_length("amp1"), Some(600));
+        assert_eq!(reference.contig_tid("amp"), None);
+
+        let bwa = BwaAligner::new(reference, BwaSettings::new(), PairedEndStats::default());
+        let seq = &chr[40200..40300];
+        let qual = vec![b'I'; seq.len()];
+        let recs = bwa.align_read(b"amp2_read", seq, &qual).unwrap();
+        assert_eq!((recs[0].tid(), recs[0].pos()), (1, 200));
+
+        assert!(BwaReference::from_sequences(&[]).is_err());
+        assert!(BwaReference::from_sequences(&[("amp 1", b"ACGT")]).is_err());
+    }
+
+    // run with `cargo test -- --ignored shared_memory_reference` on a machine with no
+    // indexes staged by `bwa shm`
+    #[test]
+    #[ignore = "stages an index in /dev/shm, and removes every staged index when done"]
+    fn shared_memory_reference() {
+        // removing our index removes every staged index, so leave others' alone
+        assert!(
+            !Path::new("/dev/shm/bwactl").exists(),
+            "indexes are already staged in shared memory; remove them with `bwa shm -d` first"
+        );
+
+        let dir = std::env::temp_dir().join(format!("rust-bwa-shm-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fasta_path = dir.join(format!("rust-bwa-shm-{}.fa", std::process::id()));
+        let chr = String::from_utf8(chr_seq()[20000..30000].to_vec()).unwrap();
+        std::fs::write(&fasta_path, format!(">chr_part\n{}\n", chr)).unwrap();
+        BwaReference::build_index(&fasta_path, IndexAlgorithm::Is).unwrap();
+
+        // staged by the first load, and loaded from shared memory by both
+        for _ in 0..2 {
+            let reference = BwaReference::open_shm(&fasta_path).unwrap();
+            assert_eq!(reference.contig_names(), &["chr_part"]);
+            let bwa = BwaAligner::new(reference, BwaSettings::new(), PairedEndStats::default());
+            let seq = &chr.as_bytes()[5000..5100];
+            let recs = bwa.align_read(b"read", seq, &[b'I'; 100]).unwrap();
+            assert_eq!((recs[0].tid(), recs[0].pos()), (0, 5000));
+        }
+
+        unsafe {
+            assert_eq!(bwa_sys::bwa_shm_destroy(), 0);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mmap_reference() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-mmap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fasta_path = dir.join("chr_part.fa");
+        let chr = String::from_utf8(chr_seq()[20000..30000].to_vec()).unwrap();
+        std::fs::write(&fasta_path, format!(">chr_part\n{}\n", chr)).unwrap();
+        BwaReference::build_index(&fasta_path, IndexAlgorithm::Is).unwrap();
+
+        let image = dir.join("chr_part.img");
+        assert!(BwaReference::open_mmap(&fasta_path, &image).is_err());
+        BwaReference::write_image(&fasta_path, &image).unwrap();
+
+        for _ in 0..2 {
+            let reference = BwaReference::open_mmap(&fasta_path, &image).unwrap();
+            assert_eq!(reference.contig_names(), &["chr_part"]);
+            let bwa = BwaAligner::new(reference, BwaSettings::new(), PairedEndStats::default());
+            let seq = &chr.as_bytes()[5000..5100];
+            let recs = bwa.align_read(b"read", seq, &[b'I'; 100]).unwrap();
+            assert_eq!((recs[0].tid(), recs[0].pos()), (0, 5000));
+        }
+
+        let mapped = BwaReference::open_mmap(&fasta_path, &image).unwrap();
+        let loaded = BwaReference::open(&fasta_path).unwrap();
+        assert_eq!(mapped.contig_lengths(), loaded.contig_lengths());
+
+        // images with a bad header, or whose length doesn't match the index they hold,
+        // are refused rather than handed to BWA
+        let data = std::fs::read(&image).unwrap();
+        let with_len = |mut d: Vec<u8>| {
+            let l_mem = (d.len() - IMAGE_HEADER_LEN) as u64;
+            d[16..IMAGE_HEADER_LEN].copy_from_slice(&l_mem.to_ne_bytes());
+            d
+        };
+        let mut bad_magic = data.clone();
+        bad_magic[0] ^= 0xff;
+        let mut bad_version = data.clone();
+        bad_version[8] ^= 0xff;
+        let mut extended = data.clone();
+        extended.push(0);
+        let bad_images = vec![
+            data[..10].to_vec(),
+            bad_magic,
+            bad_version,
+            data[..data.len() - 1].to_vec(),
+            with_len(data[..data.len() - 1].to_vec()),
+            with_len(extended),
+        ];
+        let bad_path = dir.join("bad.img");
+        for bad in &bad_images {
+            std::fs::write(&bad_path, bad).unwrap();
+            assert!(BwaReference::open_mmap(&fasta_path, &bad_path).is_err());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_with_prefix() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-index-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("idx")).unwrap();
+
+        let fasta = std::fs::read_to_string("tests/test_ref.fa").unwrap();
+        let phix: String = fasta
+            .lines()
+            .skip(1)
+            .take_while(|l| !l.starts_with('>'))
+            .collect();
+        let fasta_path = dir.join("phix.fa");
+        std::fs::write(&fasta_path, format!(">PhiX\n{}\n", phix)).unwrap();
+
+        let prefix = dir.join("idx").join("phix");
+        BwaReference::index(&fasta_path, Some(&prefix), IndexAlgorithm::Auto).unwrap();
+        assert!(prefix.with_extension("bwt").is_file());
+        assert!(!fasta_path.with_extension("fa.bwt").exists());
+
+        let bwa = BwaAligner::from_path(&prefix).unwrap();
+        let seq = &phix.as_bytes()[2000..2100];
+        let qual = vec![b'I'; seq.len()];
+        let recs = bwa.align_read(b"phix_read", seq, &qual).unwrap();
+        assert_eq!(recs[0].tid(), 0);
+        assert_eq!(recs[0].pos(), 2000);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_index_files() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-missing-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // copy the index without the suffix array, and named by a prefix without the .fa
+        for ext in &["bwt", "pac", "ann", "amb"] {
+            std::fs::copy(
+                format!("tests/test_ref.fa.{}", ext),
+                dir.join(format!("ref.{}", ext)),
+            )
+            .unwrap();
+        }
+
+        let err = BwaReference::open(dir.join("ref.fa")).err().unwrap();
+        let msg = err.to_string();
+        assert!(msg.contains(&dir.join("ref.sa").display().to_string()));
+        assert!(!msg.contains("ref.bwt"));
+        assert!(msg.contains("bwa index"));
+
+        // the contig metadata can still be loaded without the suffix array
+        let reference = BwaReference::open_with_flags(dir.join("ref"), IdxLoadFlags::BNS).unwrap();
+        assert_eq!(reference.contig_names().len(), 2);
+
+        std::fs::copy("tests/test_ref.fa.sa", dir.join("ref.sa")).unwrap();
+        assert!(BwaReference::open(dir.join("ref.fa")).is_ok());
+        assert!(BwaReference::open(dir.join("ref")).is_ok());
+
+        // each missing sidecar file is named on its own
+        for ext in &["pac", "ann", "amb", "sa"] {
+            let file = dir.join(format!("ref.{}", ext));
+            let moved = dir.join(format!("moved.{}", ext));
+            std::fs::rename(&file, &moved).unwrap();
+            let msg = BwaReference::open(dir.join("ref.fa"))
+                .err()
+                .unwrap()
+                .to_string();
+            assert!(msg.contains(&file.display().to_string()), "{}", msg);
+            for other in &["bwt", "pac", "ann", "amb", "sa"] {
+                if other != ext {
+                    assert!(!msg.contains(&format!("ref.{}", other)), "{}", msg);
+                }
+            }
+            std::fs::rename(&moved, &file).unwrap();
+        }
+
+        let err = BwaReference::open(dir.join("nonexistent.fa"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("nonexistent.fa.bwt"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fasta_mismatch() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-mismatch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let chr = chr_seq();
+        let fasta_path = dir.join("ref.fa");
+        let fai_path = dir.join("ref.fa.fai");
+
+        let mut fasta = b">a\n".to_vec();
+        fasta.extend_from_slice(&chr[0..2000]);
+        fasta.extend_from_slice(b"\n>b\n");
+        fasta.extend_from_slice(&chr[5000..6000]);
+        fasta.push(b'\n');
+        std::fs::write(&fasta_path, &fasta).unwrap();
+        BwaReference::build_index(&fasta_path, IndexAlgorithm::Is).unwrap();
+        assert!(BwaReference::open(&fasta_path).is_ok());
+
+        std::fs::write(
+            &fai_path,
+            "a\t2000\t3\t2000\t2001\nb\t1000\t2008\t1000\t1001\n",
+        )
+        .unwrap();
+        assert!(BwaReference::open(&fasta_path).is_ok());
+
+        let bad_fais = [
+            ("a\t2000\t3\t2000\t2001\n", "has 1"),
+            (
+                "a\t2000\t3\t2000\t2001\nc\t1000\t2008\t1000\t1001\n",
+                "contig 1 is b",
+            ),
+            (
+                "a\t2000\t3\t2000\t2001\nb\t999\t2008\t999\t1000\n",
+                "(999 bp)",
+            ),
+        ];
+        for &(fai, expected) in &bad_fais {
+            std::fs::write(&fai_path, fai).unwrap();
+            let msg = BwaReference::open(&fasta_path).err().unwrap().to_string();
+            assert!(msg.contains("doesn't match"), "{}", msg);
+            assert!(msg.contains(expected), "{}", msg);
+        }
+
+        // without a .fai, a fasta too small for the index is caught
+        std::fs::remove_file(&fai_path).unwrap();
+        std::fs::write(&fasta_path, ">a\nACGT\n").unwrap();
+        let msg = BwaReference::open(&fasta_path).err().unwrap().to_string();
+        assert!(msg.contains("has 3000 bases"), "{}", msg);
+
+        // with the fasta gone, there's nothing to check the index against
+        std::fs::remove_file(&fasta_path).unwrap();
+        assert!(BwaReference::open(&fasta_path).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_64() {
+        let info = load_aligner().reference().index_info().clone();
+        assert_eq!(
+            info,
+            IndexInfo {
+                prefix: PathBuf::from("tests/test_ref.fa"),
+                is_64: false,
+            }
+        );
+
+        // `bwa index -6` only changes the names of the index files
+        let dir = std::env::temp_dir().join(format!("rust-bwa-64-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for ext in &["bwt", "sa", "pac", "ann", "amb"] {
+            std::fs::copy(
+                format!("tests/test_ref.fa.{}", ext),
+                dir.join(format!("ref.fa.64.{}", ext)),
+            )
+            .unwrap();
+        }
+
+        let reference = BwaReference::open(dir.join("ref.fa")).unwrap();
+        assert_eq!(reference.contig_names(), &["PhiX", "chr"]);
+        assert_eq!(
+            reference.index_info(),
+            &IndexInfo {
+                prefix: dir.join("ref.fa.64"),
+                is_64: true,
+            }
+        );
+        let info = find_index_prefix(&dir.join("ref.fa"), IdxLoadFlags::BNS).unwrap();
+        assert!(info.is_64);
+
+        // the .64 index is preferred when both are present
+        for ext in &["bwt", "sa", "pac", "ann", "amb"] {
+            std::fs::copy(
+                format!("tests/test_ref.fa.{}", ext),
+                dir.join(format!("ref.fa.{}", ext)),
+            )
+            .unwrap();
+        }
+        let info = find_index_prefix(&dir.join("ref.fa"), IdxLoadFlags::ALL).unwrap();
+        assert_eq!(info.prefix, dir.join("ref.fa.64"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn alt_contigs() {
+        // main_alt is an exact copy of main[1000..2000]
+        let chr = chr_seq();
+        let (main, alt) = (&chr[100000..103000], &chr[101000..102000]);
+        let dir = std::env::temp_dir().join(format!("rust-bwa-alt-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut fasta = b">main\n".to_vec();
+        fasta.extend_from_slice(main);
+        fasta.extend_from_slice(b"\n>main_alt\n");
+        fasta.extend_from_slice(alt);
+        fasta.push(b'\n');
+        std::fs::write(dir.join("ref.fa"), &fasta).unwrap();
+        BwaReference::build_index(dir.join("ref.fa"), IndexAlgorithm::Is).unwrap();
+
+        let seq = &main[1200..1300];
+        let qual = vec![b'I'; seq.len()];
+        let align = |settings: BwaSettings| {
+            let reference = BwaReference::open(dir.join("ref.fa")).unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            bwa.align_read(b"read", seq, &qual).unwrap()
+        };
+
+        // without the .alt file, the read is in an exact repeat
+        let reference = BwaReference::open(dir.join("ref.fa")).unwrap();
+        assert!(!reference.is_alt_contig(1));
+        assert_eq!(align(BwaSettings::new())[0].mapq(), 0);
+
+        std::fs::write(
+            dir.join("ref.fa.alt"),
+            "main_alt\t0\tmain\t1001\t60\t1000M\t*\t0\t0\t*\t*\n",
+        )
+        .unwrap();
+        let reference = BwaReference::open(dir.join("ref.fa")).unwrap();
+        assert!(!reference.is_alt_contig(0));
+        assert!(reference.is_alt_contig(1));
+        assert!(!reference.is_alt_contig(2));
+        assert!(!reference.is_alt_contig(-1));
+
+        // the ALT hit doesn't count against the primary assembly hit, and is reported
+        // in the XA tag, or as a secondary alignment with -a
+        let recs = align(BwaSettings::new());
+        assert_eq!(recs.len(), 1);
+        assert_eq!((recs[0].tid(), recs[0].pos()), (0, 1200));
+        assert!(recs[0].mapq() > 0);
+        let hits = parse_xa(&recs[0], &reference).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].tid, hits[0].pos), (1, 200));
+
+        let recs = align(BwaSettings::new().set_output_all());
+        assert_eq!(recs.len(), 2);
+        assert_eq!((recs[0].tid(), recs[0].pos()), (0, 1200));
+        assert!(recs[1].is_secondary());
+        assert_eq!((recs[1].tid(), recs[1].pos()), (1, 200));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn nul_in_path() {
+        // an error rather than a panic
+        assert!(BwaReference::open("tests/test\0ref.fa").is_err());
+        assert!(BwaReference::build_index("tests/test\0ref.fa", IndexAlgorithm::Auto).is_err());
+        assert!(path_cstring(Path::new("tests/test\0ref.fa"))
+            .unwrap_err()
+            .to_string()
+            .contains("nul byte"));
+    }
+
+    #[test]
+    fn build_index_missing_fasta() {
+        let res = BwaReference::build_index("tests/missing.fa", IndexAlgorithm::Auto);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn light_align() {
+        let bwa = load_aligner();
+
+        for r in &[read_simple(), read_split()] {
+            let (recs1, recs2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+            let (alns1, alns2) = bwa
+                .align_read_pair_light(r[0], r[1], r[2], r[3], r[4])
+                .unwrap();
+            assert_eq!(recs1.len(), alns1.len());
+            assert_eq!(recs2.len(), alns2.len());
+
+            for (rec, aln) in recs1.iter().chain(&recs2).zip(alns1.iter().chain(&alns2)) {
+                assert_eq!(rec.tid(), aln.tid);
+                assert_eq!(rec.pos(), aln.pos);
+                assert_eq!(rec.mapq(), aln.mapq);
+                assert_eq!(rec.flags(), aln.flags);
+                assert_eq!(rec.cigar_len() as u32, aln.cigar_len);
+                assert_eq!(rec.is_secondary(), aln.is_secondary());
+            }
+        }
+
+        let mut contig_tids = HashMap::new();
+        contig_tids.insert(b"chr".to_vec(), 1);
+        let aln = BwaAlignment::from_sam(&contig_tids, b"r1\t16\tchr\t101\t60\t2S4M\t*").unwrap();
+        assert_eq!((aln.tid, aln.pos, aln.mapq, aln.flags), (1, 100, 60, 16));
+        assert_eq!(aln.cigar_len, 2);
+
+        let bad: &[&[u8]] = &[
+            b"r1\t0\tchrX\t1\t60\t4M\t*\t0\t0\tACGT\tIIII",
+            b"r1\t0\tchr\tx\t60\t4M\t*\t0\t0\tACGT\tIIII",
+            b"r1\t0\tchr\t1",
+        ];
+        for sam in bad {
+            match BwaAlignment::from_sam(&contig_tids, sam) {
+                Err(AlignError::MalformedSam(_)) => (),
+                res => panic!("expected malformed SAM error, got {:?}", res),
+            }
+        }
+    }
+
+    fn align_read(r: [&[u8]; 5]) -> (Vec<Record>, Vec<Record>) {
+        let bwa = load_aligner();
+        align_read_with(&bwa, r)
+    }
+
+    fn align_read_with(bwa: &BwaAligner, r: [&[u8]; 5]) -> (Vec<Record>, Vec<Record>) {
+        bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap()
+    }
+
+    #[test]
+    fn records_match_htslib_sam_parser() {
+        let unmapped: [&[u8]; 5] = [b"unmapped", &[b'N'; 100], &[b'I'; 100], &[b'N'; 100], b""];
+        let settings: [fn() -> BwaSettings; 2] = [BwaSettings::new, || {
+            BwaSettings::new()
+                .set_output_all()
+                .set_softclip_supplementary()
+        }];
+
+        for settings in &settings {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let mut bwa = BwaAligner::new(reference, settings(), PairedEndStats::default());
+            bwa.set_read_group("@RG\tID:rg1\tSM:sample1").unwrap();
+
+            for r in &[read_simple(), read_split(), unmapped] {
+                let recs = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+                let htslib_recs = bwa
+                    .align_read_pair_via_sam(r[0], r[1], r[2], r[3], r[4])
+                    .unwrap();
+                assert_eq!(recs, htslib_recs);
+            }
+        }
+    }
+
+    #[test]
+    fn simple_align() {
+        let (r1, r2) = align_read(read_simple());
+        assert_eq!(r1[0].pos(), 727806);
+        assert_eq!(r2[0].pos(), 727435);
+    }
+
+    #[test]
+    fn split_align() {
+        let (r1, r2) = align_read(read_split());
+        assert_eq!(r1.len(), 2);
+        assert_eq!(r1[0].pos(), 931375);
+        assert_eq!(r1[1].pos(), 932605);
+        assert_eq!(r2[0].pos(), 932937);
+    }
+
+    #[test]
+    fn split_align_sa_tag() {
+        let bwa = load_aligner();
+        let (r1, _) = align_read_with(&bwa, read_split());
+        assert_eq!(r1.len(), 2);
+
+        // each part of the chimeric alignment lists the other in its SA tag
+        for (rec, other) in [(&r1[0], &r1[1]), (&r1[1], &r1[0])] {
+            let hits = parse_sa(rec, bwa.reference()).unwrap();
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].tid, other.tid());
+            assert_eq!(hits[0].pos, other.pos());
+            assert_eq!(hits[0].is_reverse, other.is_reverse());
+            assert_eq!(hits[0].mapq, Some(other.mapq()));
+        }
+
+        // the primary's SA tag gives the supplementary's CIGAR, with the clips soft
+        let cigar = parse_sa(&r1[0], bwa.reference()).unwrap()[0].cigar.clone();
+        assert!(!cigar.to_string().contains('H'));
+        assert_eq!(
+            cigar.to_string(),
+            r1[1].cigar().to_string().replace('H', "S")
+        );
+        assert!(parse_xa(&r1[0], bwa.reference()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn xa_tag() {
+        // the read is in a 3-copy exact repeat, at 15400, 607243 and 2512308 on chr
+        let seq = &chr_seq()[15400..15500];
+        let qual = vec![b'I'; seq.len()];
+        let bwa = load_aligner();
+        let recs = bwa.align_read(b"repeat", seq, &qual).unwrap();
+
+        let hits = parse_xa(&recs[0], bwa.reference()).unwrap();
+        assert_eq!(hits.len(), 2);
+        let mut positions: Vec<_> = hits.iter().map(|h| h.pos).collect();
+        positions.push(recs[0].pos());
+        positions.sort();
+        assert_eq!(positions, vec![15400, 607243, 2512308]);
+        for hit in &hits {
+            assert_eq!(hit.tid, bwa.reference().contig_tid("chr").unwrap());
+            assert!(!hit.is_reverse);
+            assert_eq!(hit.cigar.to_string(), "100M");
+            assert_eq!(hit.nm, 0);
+            assert_eq!(hit.mapq, None);
+        }
+
+        let mut rec = recs[0].clone();
+        rec.remove_aux(b"XA").unwrap();
+        rec.push_aux(b"XA", Aux::String("chr,+1001,50M,2;"))
+            .unwrap();
+        let hits = parse_xa(&rec, bwa.reference()).unwrap();
+        assert_eq!((hits.len(), hits[0].pos, hits[0].nm), (1, 1000, 2));
+
+        rec.remove_aux(b"XA").unwrap();
+        rec.push_aux(b"XA", Aux::String("chrZ,+1001,50M,2;"))
+            .unwrap();
+        let err = parse_xa(&rec, bwa.reference()).unwrap_err();
+        assert!(err.to_string().contains("chrZ"));
+
+        rec.remove_aux(b"XA").unwrap();
+        rec.push_aux(b"XA", Aux::String("")).unwrap();
+        assert!(parse_xa(&rec, bwa.reference()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_align_filtered() {
+        let bwa = load_aligner();
+        let r = read_split();
+        let align = |filter| {
+            bwa.align_read_pair_filtered(filter, r[0], r[1], r[2], r[3], r[4])
+                .unwrap()
+        };
+
+        let (r1, r2) = align(RecordFilter::PrimaryOnly);
+        assert_eq!(r1.len(), 1);
+        assert_eq!(r1[0].pos(), 931375);
+        assert_eq!(r1[0].flags() & (0x100 | 0x800), 0);
+        assert_eq!(r2.len(), 1);
+
+        let all = align(RecordFilter::All);
+        assert_eq!(all.0.len(), 2);
+        assert_eq!(
+            all,
+            bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap()
+        );
+        assert_eq!(align(RecordFilter::MappedOnly), all);
+
+        let (r1, _) = align(RecordFilter::MapqAtLeast(255));
+        assert!(r1.is_empty());
+    }
+
+    #[test]
+    fn unmapped_pair_filtered() {
+        let bwa = load_aligner();
+        let seq = vec![b'N'; 100];
+        let qual = vec![b'I'; 100];
+        let (r1, r2) = bwa
+            .align_read_pair_filtered(RecordFilter::MappedOnly, b"pair", &seq, &qual, &seq, &qual)
+            .unwrap();
+        assert!(r1.is_empty() && r2.is_empty());
+
+        let (r1, _) = bwa
+            .align_read_pair_filtered(RecordFilter::PrimaryOnly, b"pair", &seq, &qual, &seq, &qual)
+            .unwrap();
+        assert!(r1[0].is_unmapped());
+    }
+
+    #[test]
+    fn align_owned_pair() {
+        let bwa = load_aligner();
+        let r = read_split();
+        let pair = ReadPair {
+            name: r[0].to_vec(),
+            r1: r[1].to_vec(),
+            q1: r[2].to_vec(),
+            r2: r[3].to_vec(),
+            q2: r[4].to_vec(),
+        };
+
+        let aln = bwa.align(&pair).unwrap();
+        let (r1, r2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        assert_eq!((&aln.r1, &aln.r2), (&r1, &r2));
+        assert_eq!(aln.primary_r1().pos(), 931375);
+        assert_eq!(aln.primary_r2().pos(), 932937);
+
+        let fq = |seq: &[u8], qual: &[u8]| fastq::FastqRecord {
+            name: r[0].to_vec(),
+            seq: seq.to_vec(),
+            qual: qual.to_vec(),
+            comment: None,
+        };
+        assert_eq!(ReadPair::from((fq(r[1], r[2]), fq(r[3], r[4]))), pair);
+    }
+
+    #[test]
+    fn split_align_no_multi() {
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_no_multi();
+        assert!(settings.command_line().ends_with(" -M"));
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+
+        let (r1, _) = align_read_with(&bwa, read_split());
+        assert_eq!(r1.len(), 2);
+        assert!(!r1[0].is_secondary() && !r1[0].is_supplementary());
+        assert_eq!(r1[1].pos(), 932605);
+        assert!(r1[1].is_secondary());
+        assert!(!r1[1].is_supplementary());
+    }
+
+    #[test]
+    fn split_align_softclip() {
+        let r = read_split();
+        let is_hard_clip = |op: &Cigar| matches!(*op, Cigar::HardClip(_));
+
+        // by default the supplementary alignment is hard-clipped, dropping the clipped bases
+        let (r1, _) = align_read_with(&load_aligner(), r);
+        assert!(r1[1].is_supplementary());
+        assert!(r1[1].cigar().iter().any(is_hard_clip));
+        assert!(r1[1].seq().len() < r[1].len());
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_softclip_supplementary();
+        assert!(settings.command_line().ends_with(" -Y"));
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+
+        let (r1, _) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        assert_eq!(r1.len(), 2);
+        assert!(r1[1].is_supplementary());
+
+        let cigar = r1[1].cigar();
+        assert!(cigar.iter().any(|op| matches!(*op, Cigar::SoftClip(_))));
+        assert!(!cigar.iter().any(is_hard_clip));
+        assert_eq!(r1[1].seq().len(), r[1].len());
+    }
+
+    #[test]
+    fn interleaved_align() {
+        let bwa = load_aligner();
+        let r = read_simple();
+        let batch = vec![
+            (r[0].to_vec(), r[1].to_vec(), r[2].to_vec()),
+            (r[0].to_vec(), r[3].to_vec(), r[4].to_vec()),
+        ];
+
+        let pairs = bwa.align_interleaved(&batch, 1).unwrap();
+        assert_eq!(pairs.len(), 1);
+        let (r1, r2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        assert_eq!(pairs[0], (r1, r2));
+        assert_eq!(pairs[0].0[0].pos(), 727806);
+        assert!(pairs[0].0[0].is_first_in_template());
+        assert!(pairs[0].1[0].is_last_in_template());
+
+        match bwa.align_interleaved(&batch[..1], 1) {
+            Err(AlignError::OddInterleavedBatch(1)) => (),
+            res => panic!("expected odd batch error, got {:?}", res),
+        }
+
+        assert!(BwaSettings::new()
+            .set_smart_pairing()
+            .command_line()
+            .ends_with(" -p"));
+    }
+
+    #[test]
+    fn bwa_version() {
+        // read from the bwa submodule when bwa-sys is built
+        assert_eq!(version(), bwa_sys::bwa_version());
+        assert!(version().starts_with("0.7."), "version {}", version());
+    }
+
+    #[test]
+    fn batch_single_end_align() {
+        let bwa = load_aligner();
+        let simple = read_simple();
+        let split = read_split();
+        let batch = vec![
+            (simple[0].to_vec(), simple[1].to_vec(), simple[2].to_vec()),
+            (b"unmapped".to_vec(), vec![b'N'; 100], vec![b'I'; 100]),
+            (split[0].to_vec(), split[1].to_vec(), split[2].to_vec()),
+            (simple[0].to_vec(), simple[3].to_vec(), simple[4].to_vec()),
+        ];
+
+        let recs = bwa.align_reads(&batch, 2).unwrap();
+        assert_eq!(recs.len(), batch.len());
+        assert_eq!(recs[0].len(), 1);
+        assert_eq!(recs[0][0].pos(), 727806);
+        assert_eq!(recs[1].len(), 1);
+        assert!(recs[1][0].is_unmapped());
+        assert_eq!(recs[1][0].qname(), b"unmapped");
+        let mut split_pos: Vec<_> = recs[2].iter().map(|r| r.pos()).collect();
+        split_pos.sort();
+        assert_eq!(split_pos, vec![931375, 932605]);
+        assert_eq!(recs[3].len(), 1);
+
+        // the same records as aligning each read alone
+        for ((name, seq, qual), batch_recs) in batch.iter().zip(&recs) {
+            assert_eq!(&bwa.align_read(name, seq, qual).unwrap(), batch_recs);
+            for rec in batch_recs {
+                assert_eq!(rec.flags() & (0x1 | 0x40 | 0x80), 0);
+            }
+        }
+
+        assert!(bwa.align_reads(&[], 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn single_end_align() {
+        let bwa = load_aligner();
+        let r = read_simple();
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        assert_eq!(recs.len(), 1);
+        assert_eq!(recs[0].pos(), 727806);
+
+        for rec in &recs {
+            assert_eq!(rec.flags() & (0x1 | 0x40 | 0x80), 0);
+        }
+    }
+
+    #[test]
+    fn single_end_split_align() {
+        let bwa = load_aligner();
+        let r = read_split();
+        let recs = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        assert_eq!(recs.len(), 2);
+
+        assert_eq!(recs[0].pos(), 931375);
+        assert!(!recs[0].is_secondary());
+        assert!(!recs[0].is_supplementary());
+
+        assert_eq!(recs[1].pos(), 932605);
+        assert!(!recs[1].is_secondary());
+        assert!(recs[1].is_supplementary());
+    }
+
+    #[test]
+    fn batch_align() {
+        let bwa = load_aligner();
+        let batch = vec![
+            to_read_pair(read_split()),
+            to_read_pair(read_simple()),
+            to_read_pair(read_split()),
+        ];
+
+        let results = bwa.align_read_pairs(&batch, 1).unwrap();
+        assert_eq!(results.len(), 3);
+
+        for &i in &[0, 2] {
+            let (ref r1, ref r2) = results[i];
+            assert_eq!(r1.len(), 2);
+            assert_eq!(r1[0].pos(), 931375);
+            assert_eq!(r1[1].pos(), 932605);
+            assert_eq!(r2[0].pos(), 932937);
+        }
+
+        let (ref r1, ref r2) = results[1];
+        assert_eq!(r1[0].pos(), 727806);
+        assert_eq!(r2[0].pos(), 727435);
+
+        assert!(bwa.align_read_pairs(&[], 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn batch_align_threaded() {
+        let bwa = load_aligner();
+        let batch: Vec<_> = (0..50)
+            .map(|i| {
+                if i % 2 == 0 {
+                    to_read_pair(read_simple())
+                } else {
+                    to_read_pair(read_split())
+                }
+            })
+            .collect();
+
+        let single = bwa.align_read_pairs(&batch, 1).unwrap();
+        let threaded = bwa.align_read_pairs(&batch, 4).unwrap();
+        assert_eq!(single.len(), batch.len());
+        assert_eq!(single, threaded);
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_num_threads(4);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        assert_eq!(single, bwa.align_read_pairs(&batch, 0).unwrap());
+    }
 
-        [name, r1, q1, r2, q2]
+    fn write_fastqs(dir: &Path, gzip: bool) -> (PathBuf, PathBuf, PathBuf) {
+        let ext = if gzip { "fq.gz" } else { "fq" };
+        let paths = (
+            dir.join(format!("r1.{}", ext)),
+            dir.join(format!("r2.{}", ext)),
+            dir.join(format!("interleaved.{}", ext)),
+        );
+
+        let mut r1_fq = Vec::new();
+        let mut r2_fq = Vec::new();
+        let mut interleaved_fq = Vec::new();
+        for r in &[read_simple(), read_split()] {
+            // the test read names already start with '@'
+            let rec1 = [r[0], b"/1\n", r[1], b"\n+\n", r[2], b"\n"].concat();
+            let rec2 = [r[0], b"/2\n", r[3], b"\n+\n", r[4], b"\n"].concat();
+            r1_fq.extend_from_slice(&rec1);
+            r2_fq.extend_from_slice(&rec2);
+            interleaved_fq.extend_from_slice(&rec1);
+            interleaved_fq.extend_from_slice(&rec2);
+        }
+
+        for (path, data) in &[
+            (&paths.0, r1_fq),
+            (&paths.1, r2_fq),
+            (&paths.2, interleaved_fq),
+        ] {
+            let mut file = File::create(path).unwrap();
+            if gzip {
+                let mut enc = GzEncoder::new(file, Compression::default());
+                enc.write_all(data).unwrap();
+                enc.finish().unwrap();
+            } else {
+                file.write_all(data).unwrap();
+            }
+        }
+
+        paths
     }
 
-    fn read_split() -> [&'static [u8]; 5] {
-        let name = b"@chr_1561275_1561756_1:0:0_2:0:0_5c/1";
-        let r1 = b"GCATCGATAAGCAGGTCAAATTCTCCCGTCATTATCACCTCTGCTACTTAAATTTCCCGCTTTATAAGCCGATTACGGCCTGGCATTACCCTATCCATAATTTAGGTGGGATGCCCGGTGCGTGGTTGGCAGATCCGCTGTTCTTTATTT";
-        let q1 = b"222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222";
+    #[test]
+    fn fastq_align() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-fastq-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bwa = load_aligner();
 
-        let r2 = b"TCATCGACCCAGGTATCATCGCGACGGGTACGATTACTGGCGAAGGTGAGAATGTTTAAAATCCAGCCGCCGAGTTTTTCAGCAATGGTCACCCATGACCAACCGGTGAACAACGTGAGGGCCGCTGCCCAAACGCATAGCAGCGCAATA";
-        let q2 = b"222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222";
+        let expected: Vec<_> = [read_simple(), read_split()]
+            .iter()
+            .map(|r| {
+                bwa.align_read_pair(&r[0][1..], r[1], r[2], r[3], r[4])
+                    .unwrap()
+            })
+            .collect();
 
-        [name, r1, q1, r2, q2]
+        for &gzip in &[false, true] {
+            let (r1, r2, interleaved) = write_fastqs(&dir, gzip);
+
+            let results: Vec<_> = bwa
+                .align_fastq(&r1, &r2)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(results, expected);
+
+            let results: Vec<_> = bwa
+                .align_interleaved_fastq(&interleaved)
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(results, expected);
+        }
+
+        // read 2 FASTQ is missing the last read
+        let (r1, r2, _) = write_fastqs(&dir, false);
+        let r2_data = std::fs::read(&r2).unwrap();
+        let truncated: Vec<&[u8]> = r2_data.split(|c| *c == b'\n').take(4).collect();
+        std::fs::write(&r2, [truncated.join(&b'\n'), b"\n".to_vec()].concat()).unwrap();
+        let results: Vec<_> = bwa.align_fastq(&r1, &r2).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        match results[1] {
+            Err(FastqError::UnpairedRead) => (),
+            ref res => panic!("expected UnpairedRead error, got {:?}", res),
+        }
+
+        assert!(bwa.align_fastq(dir.join("missing.fq"), r2).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
-    fn align_read(r: [&[u8]; 5]) -> (Vec<Record>, Vec<Record>) {
+    #[test]
+    fn align_pairs_from_fastq() {
+        let bwa = load_aligner();
+        let pairs: Vec<ReadPair> =
+            FastqPairReader::from_paths("tests/pair_1.fq.gz", "tests/pair_2.fq.gz")
+                .unwrap()
+                .map(|pair| pair.unwrap().into())
+                .collect();
+        assert_eq!(pairs.len(), 2);
+
+        let expected: Vec<_> = pairs.iter().map(|p| bwa.align(p).unwrap()).collect();
+        for &batch in &[1, 2, 10] {
+            let aligned: Vec<_> = bwa
+                .align_pairs_from(pairs.clone(), batch)
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(aligned, expected);
+        }
+        assert_eq!(bwa.align_pairs_from(Vec::new(), 10).count(), 0);
+    }
+
+    #[test]
+    fn fastq_pair_to_bam() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-fastq-bam-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bam_path = dir.join("out.bam");
         let bwa = load_aligner();
-        bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4])
+
+        let (r1, r2, _) = write_fastqs(&dir, true);
+        let stats = bwa.align_fastq_pair_to_bam(&r1, &r2, &bam_path, 2).unwrap();
+
+        let expected: Vec<_> = bwa
+            .align_fastq(&r1, &r2)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        let mut expected_stats = AlignStats::default();
+        for (recs1, recs2) in &expected {
+            expected_stats.add_pair(recs1, recs2);
+        }
+        assert_eq!(stats, expected_stats);
+        assert_eq!(stats.pairs, 2);
+        assert_eq!(stats.mapped, 2);
+
+        let mut reader = bam::Reader::from_path(&bam_path).unwrap();
+        let header = String::from_utf8(reader.header().as_bytes()).unwrap();
+        assert!(header.contains("@SQ\tSN:chr\tLN:4639675"));
+        assert!(header.contains("@PG\tID:bwa"));
+
+        let recs: Vec<_> = reader.records().collect::<Result<_, _>>().unwrap();
+        let n_expected: usize = expected.iter().map(|(r1, r2)| r1.len() + r2.len()).sum();
+        assert_eq!(recs.len(), n_expected);
+
+        // a small chunk size aligns each pair in its own batch, with the same results.
+        // The thread count doesn't affect pairs aligned one at a time.
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_chunk_size(100).set_num_threads(8);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let stats = bwa.align_fastq_pair_to_bam(&r1, &r2, &bam_path, 0).unwrap();
+        assert_eq!(stats, expected_stats);
+        let results: Vec<_> = bwa
+            .align_fastq(&r1, &r2)
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(results, expected);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn simple_align() {
-        let (r1, r2) = align_read(read_simple());
+    fn fastq_append_comment() {
+        let dir =
+            std::env::temp_dir().join(format!("rust-bwa-fastq-comment-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (r1, r2) = (dir.join("r1.fq"), dir.join("r2.fq"));
+        let bam_path = dir.join("out.bam");
+
+        // the test read names already start with '@'
+        let r = read_split();
+        let rec1 = [r[0], b"/1 BX:Z:AAAA-1\n", r[1], b"\n+\n", r[2], b"\n"].concat();
+        let rec2 = [r[0], b"/2 BX:Z:CCCC-1\n", r[3], b"\n+\n", r[4], b"\n"].concat();
+        File::create(&r1).unwrap().write_all(&rec1).unwrap();
+        File::create(&r2).unwrap().write_all(&rec2).unwrap();
+
+        // comments are dropped by default, as with bwa mem
+        let bwa = load_aligner();
+        let (recs1, recs2) = bwa.align_fastq(&r1, &r2).unwrap().next().unwrap().unwrap();
+        assert!(recs1
+            .iter()
+            .chain(&recs2)
+            .all(|rec| rec.aux(b"BX").is_err()));
+
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_append_comment();
+        assert!(settings.command_line().ends_with(" -C"));
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+
+        // each read gets its own comment
+        let (recs1, recs2) = bwa.align_fastq(&r1, &r2).unwrap().next().unwrap().unwrap();
+        assert!(recs1.len() > 1);
+        for rec in &recs1 {
+            assert_eq!(rec.aux(b"BX").unwrap(), Aux::String("AAAA-1"));
+        }
+        for rec in &recs2 {
+            assert_eq!(rec.aux(b"BX").unwrap(), Aux::String("CCCC-1"));
+        }
+
+        bwa.align_fastq_pair_to_bam(&r1, &r2, &bam_path, 1).unwrap();
+        let mut reader = bam::Reader::from_path(&bam_path).unwrap();
+        let recs: Vec<_> = reader.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(recs.len(), recs1.len() + recs2.len());
+        for rec in &recs {
+            let bx = if rec.is_first_in_template() {
+                "AAAA-1"
+            } else {
+                "CCCC-1"
+            };
+            assert_eq!(rec.aux(b"BX").unwrap(), Aux::String(bx));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn revcomp(seq: &[u8]) -> Vec<u8> {
+        seq.iter()
+            .rev()
+            .map(|c| match *c {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                b'T' => b'A',
+                c => c,
+            })
+            .collect()
+    }
+
+    /// The sequence of the `chr` contig of the test reference
+    fn chr_seq() -> Vec<u8> {
+        contig_seq("chr")
+    }
+
+    /// The sequence of contig `name` of the test reference
+    fn contig_seq(name: &str) -> Vec<u8> {
+        let fasta = std::fs::read_to_string("tests/test_ref.fa").unwrap();
+        let header = format!(">{}", name);
+        fasta
+            .lines()
+            .skip_while(|l| *l != header)
+            .skip(1)
+            .take_while(|l| !l.starts_with('>'))
+            .flat_map(|l| l.bytes())
+            .collect()
+    }
+
+    #[test]
+    fn unpaired_penalty() {
+        // read 1 matches the decoy exactly, and main with 2 mismatches 200bp from read 2
+        let chr = chr_seq();
+        let r1 = chr[200500..200600].to_vec();
+        let r2 = revcomp(&chr[200600..200700]);
+        let mut main = chr[200000..202000].to_vec();
+        for &i in &[530, 570] {
+            main[i] = if main[i] == b'A' { b'C' } else { b'A' };
+        }
+        let decoy = [&chr[500000..500400], &r1[..], &chr[500400..500800]].concat();
+        let q = vec![b'I'; 100];
+
+        let align = |unpaired: i32| {
+            let reference =
+                BwaReference::from_sequences(&[("main", &main), ("decoy", &decoy)]).unwrap();
+            let settings = BwaSettings::new().set_unpaired(unpaired);
+            assert_eq!(settings.params().pen_unpaired, unpaired);
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            let (recs1, _) = bwa.align_read_pair(b"pair", &r1, &q, &r2, &q).unwrap();
+            (recs1[0].tid(), recs1[0].pos(), recs1[0].is_proper_pair())
+        };
+
+        // without a penalty, read 1 takes its best hit; with the default penalty of 17,
+        // the paired placement scores 90 + 100 against 100 + 100 - 17 unpaired
+        assert_eq!(align(0), (1, 400, false));
+        assert_eq!(align(17), (0, 500, true));
+        assert_eq!(align(50), (0, 500, true));
+    }
+
+    #[test]
+    fn skip_pairing_and_rescue() {
+        let chr = chr_seq();
+        let frag = &chr[200000..200300];
+        let r1 = frag[..100].to_vec();
+
+        // a mismatch every 15bp leaves read 2 with no seeds, so it can only be aligned by
+        // mate rescue
+        let mut r2 = revcomp(&frag[200..]);
+        for i in (7..100).step_by(15) {
+            r2[i] = if r2[i] == b'A' { b'C' } else { b'A' };
+        }
+        let q = vec![b'I'; 100];
+
+        let align = |settings: BwaSettings| {
+            let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+            let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+            bwa.align_read_pair(b"pair", &r1, &q, &r2, &q).unwrap()
+        };
+
+        let (recs1, recs2) = align(BwaSettings::new());
+        assert!(!recs2[0].is_unmapped());
+        assert_eq!(recs2[0].pos(), 200200);
+        assert!(recs1[0].is_proper_pair());
+
+        let (recs1, recs2) = align(BwaSettings::new().set_skip_mate_rescue());
+        assert!(!recs1[0].is_unmapped());
+        assert!(recs2[0].is_unmapped());
+
+        let (_, recs2) = align(BwaSettings::new().set_max_mate_sw(0));
+        assert!(recs2[0].is_unmapped());
+        assert!(BwaSettings::new()
+            .set_max_mate_sw(10)
+            .command_line()
+            .contains(" -m 10"));
+
+        // read 2 is still rescued, but the mates' alignments are chosen independently
+        // and the pair isn't marked as proper
+        let (recs1, recs2) = align(BwaSettings::new().set_skip_pairing());
+        assert!(!recs1[0].is_unmapped());
+        assert!(!recs2[0].is_unmapped());
+        assert!(!recs1[0].is_proper_pair());
+        assert!(!recs2[0].is_proper_pair());
+
+        let cl = BwaSettings::new()
+            .set_skip_mate_rescue()
+            .set_skip_pairing()
+            .command_line();
+        assert!(cl.ends_with(" -S -P"));
+
+        // reads with seeds are still placed correctly without pairing
+        let r = read_simple();
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_skip_pairing();
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let (recs1, recs2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        let (paired1, paired2) = load_aligner()
+            .align_read_pair(r[0], r[1], r[2], r[3], r[4])
+            .unwrap();
+        assert!(paired1[0].is_proper_pair());
+        assert!(!recs1[0].is_proper_pair() && !recs2[0].is_proper_pair());
+        assert_eq!(recs1[0].pos(), paired1[0].pos());
+        assert_eq!(recs2[0].pos(), paired2[0].pos());
+    }
+
+    #[test]
+    fn align_from_threads() {
+        let chr = chr_seq();
+        let pairs: Vec<_> = (0..1000)
+            .map(|i| {
+                let frag = &chr[10000 + i * 4000..10000 + i * 4000 + 300];
+                let q = vec![b'I'; 100];
+                ReadPair {
+                    name: format!("pair{}", i).into_bytes(),
+                    r1: frag[..100].to_vec(),
+                    q1: q.clone(),
+                    r2: revcomp(&frag[200..]),
+                    q2: q,
+                }
+            })
+            .collect();
+
+        let bwa = load_aligner();
+        let align = |pairs: &[ReadPair]| -> Vec<_> {
+            pairs
+                .iter()
+                .map(|p| {
+                    bwa.align_read_pair(&p.name, &p.r1, &p.q1, &p.r2, &p.q2)
+                        .unwrap()
+                })
+                .collect()
+        };
+
+        let expected = align(&pairs);
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = pairs
+                .chunks(250)
+                .map(|chunk| scope.spawn(move || align(chunk)))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap())
+                .collect()
+        });
+        assert_eq!(results, expected);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn align_pairs_par() {
+        let chr = chr_seq();
+        let q = vec![b'I'; 100];
+        let pairs: Vec<_> = (0..1000)
+            .map(|i| {
+                let frag = &chr[10000 + i * 4000..10000 + i * 4000 + 300];
+                ReadPair {
+                    name: format!("pair{}", i).into_bytes(),
+                    r1: frag[..100].to_vec(),
+                    q1: q.clone(),
+                    r2: revcomp(&frag[200..]),
+                    q2: q.clone(),
+                }
+            })
+            .collect();
+
+        let bwa = load_aligner();
+        let expected: Vec<_> = pairs.iter().map(|p| bwa.align(p).unwrap()).collect();
+        assert_eq!(bwa.align_pairs_par(&pairs).unwrap(), expected);
+        assert!(bwa.align_pairs_par(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn shared_aligner() {
+        let bwa = std::sync::Arc::new(load_aligner());
+        let expected = align_read_with(&bwa, read_split());
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let bwa = bwa.clone();
+                std::thread::spawn(move || align_read_with(&bwa, read_split()))
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn infer_paired_end_stats() {
+        let chr = chr_seq();
+
+        // Simulate 100 forward-reverse pairs of 100bp reads with insert sizes from 350 to 449
+        let mut pairs = Vec::new();
+        let mut total_insert = 0;
+        for i in 0..100 {
+            let start = 100000 + i * 40000;
+            let insert = 350 + (i * 37) % 100;
+            total_insert += insert;
+
+            let frag = &chr[start..start + insert];
+            let r1 = frag[..100].to_vec();
+            let r2 = revcomp(&frag[insert - 100..]);
+            pairs.push(ReadPair {
+                name: format!("pair{}", i).into_bytes(),
+                r1,
+                q1: vec![b'I'; 100],
+                r2,
+                q2: vec![b'I'; 100],
+            });
+        }
+        let mean_insert = total_insert as f64 / 100.0;
+
+        let mut bwa = load_aligner();
+        let pe_stats = PairedEndStats::infer(&bwa, &pairs);
+
+        let fr = pe_stats.insert_size(PairOrientation::FR).unwrap();
+        assert!((fr.avg - mean_insert).abs() < 10.0);
+        assert!(fr.low <= 350 && fr.high >= 449);
+        assert_eq!(pe_stats.insert_size(PairOrientation::FF), None);
+        assert_eq!(pe_stats.insert_size(PairOrientation::RF), None);
+        assert_eq!(pe_stats.insert_size(PairOrientation::RR), None);
+
+        bwa.set_paired_end_stats(pe_stats);
+        let (r1, r2) = &bwa.align_read_pairs(&pairs[..1], 1).unwrap()[0];
+        assert!(r1[0].is_proper_pair());
+        assert!(r2[0].is_proper_pair());
+
+        // all the pairs are ignored when their inserts exceed the max insert size
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let settings = BwaSettings::new().set_max_insert(300);
+        let bwa = BwaAligner::new(reference, settings, PairedEndStats::default());
+        let pe_stats = PairedEndStats::infer(&bwa, &pairs);
+        assert_eq!(pe_stats.insert_size(PairOrientation::FR), None);
+    }
+
+    #[test]
+    fn shared_reference() {
+        let reference = Arc::new(BwaReference::open("tests/test_ref.fa").unwrap());
+        let lenient = BwaAligner::with_reference(
+            reference.clone(),
+            BwaSettings::new().set_min_output_score(30),
+            PairedEndStats::default(),
+        );
+        let strict = BwaAligner::with_reference(
+            reference.clone(),
+            BwaSettings::new().set_min_output_score(80),
+            PairedEndStats::default(),
+        );
+        assert_eq!(Arc::strong_count(&reference), 3);
+
+        // 60 aligned bases, scoring 60
+        let mut seq = chr_seq()[10000..10060].to_vec();
+        seq.extend_from_slice(&[b'N'; 40]);
+        let qual = vec![b'I'; seq.len()];
+
+        std::thread::scope(|scope| {
+            let lenient = scope.spawn(|| lenient.align_read(b"read", &seq, &qual).unwrap());
+            let strict = scope.spawn(|| strict.align_read(b"read", &seq, &qual).unwrap());
+
+            let recs = lenient.join().unwrap();
+            assert_eq!(recs[0].pos(), 10000);
+            assert!(strict.join().unwrap()[0].is_unmapped());
+        });
+
+        drop(lenient);
+        drop(strict);
+        assert_eq!(Arc::strong_count(&reference), 1);
+    }
+
+    #[test]
+    fn alignment_metrics() {
+        let bwa = load_aligner();
+        let batch = vec![to_read_pair(read_simple()), to_read_pair(read_split())];
+        let (pairs, metrics) = bwa.align_read_pairs_with_metrics(&batch, 1, 1000).unwrap();
+        assert_eq!(pairs, bwa.align_read_pairs(&batch, 1).unwrap());
+
+        // 4 mapped reads, with a supplementary alignment for read 1 of the split pair
+        assert_eq!(metrics.reads, 4);
+        assert_eq!(metrics.mapped, 4);
+        assert_eq!(metrics.secondary, 0);
+        assert_eq!(metrics.supplementary, 1);
+        assert_eq!(metrics.duplicates, 0);
+        assert_eq!(metrics.mapq_histogram.len(), 256);
+        assert_eq!(metrics.mapq_histogram.iter().sum::<u64>(), 4);
+        for rec in pairs.iter().flat_map(|(r1, r2)| vec![&r1[0], &r2[0]]) {
+            assert!(metrics.mapq_histogram[rec.mapq() as usize] > 0);
+        }
+
+        let primaries: Vec<_> = pairs
+            .iter()
+            .flat_map(|(r1, r2)| vec![&r1[0], &r2[0]])
+            .collect();
+        let proper: Vec<_> = primaries.iter().filter(|r| r.is_proper_pair()).collect();
+        assert_eq!(metrics.properly_paired, proper.len() as u64);
+
+        assert_eq!(metrics.insert_size_histogram.len(), 1001);
+        for rec in proper.iter().filter(|r| r.is_first_in_template()) {
+            let insert_size = (rec.insert_size().unsigned_abs() as usize).min(1000);
+            assert!(metrics.insert_size_histogram[insert_size] > 0);
+        }
+        assert_eq!(
+            metrics.insert_size_histogram.iter().sum::<u64>(),
+            proper.len() as u64 / 2
+        );
+    }
+
+    #[test]
+    fn bam_writer() {
+        let dir = std::env::temp_dir().join(format!("rust-bwa-writer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bam_path = dir.join("out.bam");
+
+        let mut bwa = load_aligner();
+        bwa.set_read_group("@RG\tID:rg1\tSM:sample1").unwrap();
+        let batch = vec![to_read_pair(read_simple()), to_read_pair(read_split())];
+        {
+            let mut writer = bwa.create_bam_writer(&bam_path).unwrap();
+            bwa.align_and_write(&batch, &mut writer).unwrap();
+        }
+
+        let mut reader = bam::Reader::from_path(&bam_path).unwrap();
+        let header = String::from_utf8(reader.header().as_bytes()).unwrap();
+        assert!(header.contains("@SQ\tSN:chr\tLN:4639675"));
+        assert!(header.contains("@RG\tID:rg1\tSM:sample1"));
+        let pg = format!(
+            "@PG\tID:bwa\tPN:bwa\tVN:{}\tCL:bwa mem -t 1 -k 19 -w 100 -d 100",
+            version()
+        );
+        assert!(header.contains(&pg));
+        assert!(header.contains("-R '@RG\\tID:rg1\\tSM:sample1'"));
+
+        let expected: usize = bwa
+            .align_read_pairs(&batch, 1)
+            .unwrap()
+            .iter()
+            .map(|(r1, r2)| r1.len() + r2.len())
+            .sum();
+        let recs: Vec<_> = reader.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(recs.len(), expected);
+        assert_eq!(recs[0].pos(), 727806);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_name() {
+        let bwa = load_aligner();
+        let r = read_simple();
+
+        match bwa.align_read_pair(b"read\0name", r[1], r[2], r[3], r[4]) {
+            Err(AlignError::InvalidName(_)) => (),
+            res => panic!("expected InvalidName error, got {:?}", res),
+        }
+
+        match bwa.align_read(b"read\0name", r[1], r[2]) {
+            Err(AlignError::InvalidName(_)) => (),
+            res => panic!("expected InvalidName error, got {:?}", res),
+        }
+
+        let mut batch = vec![to_read_pair(r), to_read_pair(r)];
+        batch[1].name = b"read\0name".to_vec();
+        match bwa.align_read_pairs(&batch, 1) {
+            Err(AlignError::InvalidName(_)) => (),
+            res => panic!("expected InvalidName error, got {:?}", res),
+        }
+
+        let reads = vec![
+            (r[0].to_vec(), r[1].to_vec(), r[2].to_vec()),
+            (b"read\0name".to_vec(), r[3].to_vec(), r[4].to_vec()),
+        ];
+        assert!(bwa.align_reads(&reads, 1).is_err());
+        assert!(bwa.align_interleaved(&reads, 1).is_err());
+
+        let mut scratch = AlignScratch::new();
+        let (mut out1, mut out2) = (Vec::new(), Vec::new());
+        let mut pair = to_read_pair(r);
+        pair.name = b"read\0name".to_vec();
+        let res = bwa.align_read_pair_into(&mut scratch, &pair, &mut out1, &mut out2);
+        let err: BwaError = res.unwrap_err().into();
+        assert!(err.to_string().starts_with("read name contains a nul byte"));
+
+        // the aligner is still usable after an error
+        let (r1, _) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
         assert_eq!(r1[0].pos(), 727806);
-        assert_eq!(r2[0].pos(), 727435);
     }
 
     #[test]
-    fn split_align() {
-        let (r1, r2) = align_read(read_split());
-        assert_eq!(r1.len(), 2);
-        assert_eq!(r1[0].pos(), 931375);
-        assert_eq!(r1[1].pos(), 932605);
-        assert_eq!(r2[0].pos(), 932937);
+    fn mismatched_lengths() {
+        let bwa = load_aligner();
+        let r = read_simple();
+
+        match bwa.align_read_pair(r[0], r[1], &r[2][1..], r[3], r[4]) {
+            Err(AlignError::MismatchedLengths { seq_len, qual_len }) => {
+                assert_eq!(seq_len, r[1].len());
+                assert_eq!(qual_len, r[2].len() - 1);
+            }
+            res => panic!("expected MismatchedLengths error, got {:?}", res),
+        }
+
+        assert!(bwa.align_read(r[0], r[1], &r[2][1..]).is_err());
+    }
+
+    #[test]
+    fn no_quality() {
+        let bwa = load_aligner();
+        let r = read_split();
+
+        let (r1, r2) = bwa.align_read_pair(r[0], r[1], b"", r[3], b"").unwrap();
+        let (r1_qual, _) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        assert_eq!(r1.len(), r1_qual.len());
+        assert_eq!(r1[0].pos(), r1_qual[0].pos());
+
+        // qualities don't affect BWA's alignments, only the records' qualities
+        let loci = |recs: &[Record]| -> Vec<_> {
+            recs.iter()
+                .map(|rec| (rec.tid(), rec.pos(), rec.cigar().to_string()))
+                .collect()
+        };
+        for r in &[read_simple(), read_split()] {
+            let (recs1, recs2) = bwa.align_read_pair(r[0], r[1], b"", r[3], b"").unwrap();
+            let (qual1, qual2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+            assert_eq!(loci(&recs1), loci(&qual1));
+            assert_eq!(loci(&recs2), loci(&qual2));
+        }
+
+        let se = bwa.align_read(r[0], r[1], b"").unwrap();
+        for rec in r1.iter().chain(&r2).chain(&se) {
+            // '*' qualities are stored as 0xff in BAM records
+            assert!(rec.qual().iter().all(|&q| q == 0xff));
+        }
+    }
+
+    #[test]
+    fn fetch_sequence() {
+        let bwa = load_aligner();
+        let reference = bwa.reference();
+        let chr = chr_seq();
+        let tid = reference.contig_tid("chr").unwrap();
+
+        // the reference at an alignment matches the read
+        let seq = &chr[10000..10100];
+        let recs = bwa.align_read(b"read", seq, b"").unwrap();
+        let pos = recs[0].pos() as u64;
+        assert_eq!(reference.fetch(tid, pos, pos + 100).unwrap(), seq);
+
+        let len = chr.len() as u64;
+        assert_eq!(
+            reference.fetch(tid, len - 10, len).unwrap(),
+            &chr[chr.len() - 10..]
+        );
+
+        // whole contigs match the FASTA, without running into the neighbouring contig
+        for (tid, name) in reference.contig_names().iter().enumerate() {
+            let len = reference.contig_lengths()[tid] as u64;
+            assert_eq!(
+                reference.fetch(tid as i32, 0, len).unwrap(),
+                contig_seq(name)
+            );
+        }
+        assert_eq!(
+            reference.fetch(0, 5376, 5386).unwrap(),
+            &contig_seq("PhiX")[5376..]
+        );
+        assert!(reference.fetch(tid, 5, 5).unwrap().is_empty());
+
+        let err = reference.fetch(tid, 100, len + 1).unwrap_err();
+        assert!(err.to_string().contains("has length 4639675"));
+        assert!(reference.fetch(tid, 200, 100).is_err());
+        assert!(reference.fetch(2, 0, 10).is_err());
+        assert!(reference.fetch(-1, 0, 10).is_err());
+
+        let bns_only =
+            BwaReference::open_with_flags("tests/test_ref.fa", IdxLoadFlags::BNS).unwrap();
+        assert!(bns_only.fetch(tid, 0, 10).is_err());
+
+        // ambiguous bases are N, rather than the random bases BWA packs in their place
+        let mut amb = chr[20000..20600].to_vec();
+        for base in &mut amb[300..310] {
+            *base = b'N';
+        }
+        amb[400] = b'R';
+        let reference = BwaReference::from_sequences(&[("amb", &amb)]).unwrap();
+        amb[400] = b'N';
+        assert_eq!(reference.fetch(0, 0, 600).unwrap(), amb);
+        assert_eq!(reference.fetch(0, 305, 315).unwrap(), &amb[305..315]);
+    }
+
+    #[test]
+    fn find_smems() {
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let chr = chr_seq();
+        let smems = |seq: &[u8]| -> Vec<_> {
+            reference
+                .find_smems(seq, 19)
+                .unwrap()
+                .iter()
+                .map(|s| (s.query_start(), s.query_end(), s.occurrences()))
+                .collect()
+        };
+
+        assert_eq!(smems(&chr[10000..10030]), [(0, 30, 1)]);
+        assert_eq!(smems(&chr[10000..10100]), [(0, 100, 1)]);
+        assert!(smems(b"").is_empty());
+
+        // the read is in a 3-copy exact repeat
+        assert_eq!(smems(&chr[15400..15500]), [(0, 100, 3)]);
+
+        // a mismatch splits the match in two
+        let mut seq = chr[10000..10100].to_vec();
+        seq[50] = if seq[50] == b'A' { b'C' } else { b'A' };
+        assert_eq!(smems(&seq), [(0, 50, 1), (51, 100, 1)]);
+
+        // matches don't span Ns, and short matches are dropped
+        let mut seq = chr[10000..10100].to_vec();
+        seq[90] = b'N';
+        assert_eq!(smems(&seq), [(0, 90, 1)]);
+
+        let bns_only =
+            BwaReference::open_with_flags("tests/test_ref.fa", IdxLoadFlags::BNS).unwrap();
+        assert!(bns_only.find_smems(&seq, 19).is_err());
+    }
+
+    #[test]
+    fn smem_hits() {
+        let reference = BwaReference::open("tests/test_ref.fa").unwrap();
+        let chr = chr_seq();
+        let hits = |seq: &[u8], max_hits| {
+            let smems = reference.find_smems(seq, 19).unwrap();
+            assert_eq!(smems.len(), 1);
+            reference.smem_hits(&smems[0], max_hits).unwrap()
+        };
+        let hit = |pos, is_reverse| SmemHit {
+            tid: 1,
+            pos,
+            is_reverse,
+        };
+
+        let seq = &chr[10000..10030];
+        assert_eq!(hits(seq, 10), [hit(10000, false)]);
+        assert_eq!(hits(&revcomp(seq), 10), [hit(10000, true)]);
+
+        // each copy of the repeat matches the read
+        let seq = &chr[15400..15500];
+        let repeat = hits(seq, 10);
+        assert_eq!(repeat.len(), 3);
+        assert!(repeat.contains(&hit(15400, false)));
+        for h in &repeat {
+            let pos = h.pos as u64;
+            assert_eq!(reference.fetch(h.tid, pos, pos + 100).unwrap(), seq);
+        }
+        assert_eq!(hits(seq, 2).len(), 2);
+
+        let bns_only = BwaReference::open_with_flags(
+            "tests/test_ref.fa",
+            IdxLoadFlags::BNS | IdxLoadFlags::PAC,
+        )
+        .unwrap();
+        let smem = reference.find_smems(seq, 19).unwrap()[0];
+        assert!(bns_only.smem_hits(&smem, 10).is_err());
+
+        // an SMEM from another reference is rejected rather than read out of bounds
+        let small = BwaReference::from_sequences(&[("small", &chr[10000..10100])]).unwrap();
+        assert!(smem.sa_begin() > 1000);
+        assert!(small.smem_hits(&smem, 10).is_err());
+    }
+
+    #[test]
+    fn contig_accessors() {
+        let bwa = load_aligner();
+        let reference = bwa.reference();
+        assert_eq!(reference.contig_names(), &["PhiX", "chr"]);
+        assert_eq!(reference.contig_lengths(), &[5386, 4639675]);
+
+        let (r1, _) = align_read_with(&bwa, read_simple());
+        assert_eq!(reference.tid_to_name(r1[0].tid()), Some("chr"));
+        assert_eq!(reference.tid_to_name(2), None);
+        assert_eq!(reference.tid_to_name(-1), None);
+
+        assert_eq!(reference.n_contigs(), 2);
+        assert_eq!(reference.contig_tid("chr"), Some(r1[0].tid()));
+        assert_eq!(reference.contig_tid("PhiX"), Some(0));
+        assert_eq!(reference.contig_tid("chrM"), None);
+        assert_eq!(reference.contig_length("PhiX"), Some(5386));
+        assert_eq!(reference.contig_length("chr"), Some(4639675));
+        assert_eq!(reference.contig_length("chrM"), None);
     }
 
     #[test]
@@ -402,4 +6744,102 @@ mod tests {
             &hdr[..]
         );
     }
+
+    #[test]
+    fn header_read_group() {
+        let mut bwa = load_aligner();
+        bwa.set_read_group("@RG\tID:rg1\tSM:sample1").unwrap();
+        let hdr = b"@SQ\tSN:PhiX\tLN:5386\n@SQ\tSN:chr\tLN:4639675\n@RG\tID:rg1\tSM:sample1";
+        assert_eq!(bwa.create_bam_header().to_bytes().as_slice(), &hdr[..]);
+
+        // escaped tabs, as passed to bwa mem -R
+        bwa.set_read_group("@RG\\tID:rg2\\tSM:sample2").unwrap();
+        let hdr = b"@SQ\tSN:PhiX\tLN:5386\n@SQ\tSN:chr\tLN:4639675\n@RG\tID:rg2\tSM:sample2";
+        assert_eq!(bwa.create_bam_header().to_bytes().as_slice(), &hdr[..]);
+
+        let mut header = bwa.create_bam_header();
+        bwa.populate_pg_header(&mut header, "rust-bwa", None);
+        bwa.populate_pg_header(&mut header, "rust-bwa.1", Some("my_pipeline --align"));
+        let header = String::from_utf8(header.to_bytes()).unwrap();
+        let pg_lines: Vec<_> = header.lines().filter(|l| l.starts_with("@PG")).collect();
+        assert_eq!(pg_lines.len(), 2);
+
+        let pg = format!(
+            "@PG\tID:rust-bwa\tPN:bwa-mem (rust-bwa)\tVN:{}\tCL:bwa mem -t 1 -k 19 ",
+            version()
+        );
+        assert!(pg_lines[0].starts_with(&pg));
+        assert!(pg_lines[0].ends_with(" -R '@RG\\tID:rg2\\tSM:sample2'"));
+        assert!(pg_lines[1].starts_with("@PG\tID:rust-bwa.1\tPN:bwa-mem (rust-bwa)\tVN:"));
+        assert!(pg_lines[1].ends_with("\tCL:my_pipeline --align"));
+
+        let header = String::from_utf8(bwa.create_bam_header_with_pg().to_bytes()).unwrap();
+        let pg = format!("\n@PG\tID:bwa\tPN:bwa\tVN:{}\tCL:bwa mem ", version());
+        assert!(header.starts_with(std::str::from_utf8(&hdr[..]).unwrap()));
+        assert!(header.contains(&pg));
+    }
+
+    #[test]
+    fn read_group_tags() {
+        let mut bwa = load_aligner();
+        bwa.set_read_group("@RG\tID:rg1\tSM:sample1").unwrap();
+        let r = read_split();
+
+        let (r1, r2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        let se = bwa.align_read(r[0], r[1], r[2]).unwrap();
+        let batch = bwa.align_read_pairs(&[to_read_pair(r)], 1).unwrap();
+        let (b1, b2) = &batch[0];
+
+        for rec in r1.iter().chain(&r2).chain(&se).chain(b1).chain(b2) {
+            assert_eq!(rec.aux(b"RG").unwrap(), Aux::String("rg1"));
+        }
+    }
+
+    #[test]
+    fn read_group_struct() {
+        let rg = ReadGroup::new("rg1")
+            .sample("sample1")
+            .library("lib1")
+            .platform("ILLUMINA");
+        assert_eq!(rg.id(), "rg1");
+        assert_eq!(
+            rg,
+            ReadGroup::parse("@RG\tID:rg1\tSM:sample1\tLB:lib1\tPL:ILLUMINA").unwrap()
+        );
+
+        let bwa = load_aligner().with_read_group(rg);
+        let mut header = Header::new();
+        header.push_comment(b"test");
+        bwa.populate_bam_header(&mut header);
+        let hdr = b"@CO\ttest\n@SQ\tSN:PhiX\tLN:5386\n@SQ\tSN:chr\tLN:4639675\n@RG\tID:rg1\tSM:sample1\tLB:lib1\tPL:ILLUMINA";
+        assert_eq!(header.to_bytes().as_slice(), &hdr[..]);
+
+        let r = read_simple();
+        let (r1, r2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        for rec in r1.iter().chain(&r2) {
+            assert_eq!(rec.aux(b"RG").unwrap(), Aux::String("rg1"));
+        }
+    }
+
+    #[test]
+    fn no_read_group() {
+        let bwa = load_aligner();
+        let hdr = b"@SQ\tSN:PhiX\tLN:5386\n@SQ\tSN:chr\tLN:4639675";
+        assert_eq!(bwa.create_bam_header().to_bytes().as_slice(), &hdr[..]);
+
+        let r = read_simple();
+        let (r1, r2) = bwa.align_read_pair(r[0], r[1], r[2], r[3], r[4]).unwrap();
+        for rec in r1.iter().chain(&r2) {
+            assert!(rec.aux(b"RG").is_err());
+        }
+    }
+
+    #[test]
+    fn read_group_malformed() {
+        let mut bwa = load_aligner();
+        assert!(bwa.set_read_group("@RG\tSM:sample1").is_err());
+        assert!(bwa.set_read_group("@CO\tID:rg1").is_err());
+        assert!(bwa.set_read_group("@RG\tID").is_err());
+        assert!(bwa.set_read_group("").is_err());
+    }
 }
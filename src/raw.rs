@@ -0,0 +1,265 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+//! Plain Rust alignment records, parsed from the SAM lines written by BWA without
+//! going through htslib. These are available with or without the `htslib` feature,
+//! for callers that want alignments as plain data.
+
+use std::collections::HashMap;
+use std::str::{self, FromStr};
+
+use AlignError;
+
+/// One SAM record written by BWA, with the fields parsed into plain Rust types.
+/// Returned by `BwaAligner::align_read_pair_raw`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamRecordOwned {
+    pub qname: Vec<u8>,
+    /// SAM flags
+    pub flag: u16,
+    /// Reference sequence id, or -1 if unmapped
+    pub tid: i32,
+    /// 0-based leftmost position, or -1 if unmapped
+    pub pos: i64,
+    pub mapq: u8,
+    /// CIGAR string, or `*` if unmapped
+    pub cigar: String,
+    /// Reference sequence id of the mate, or -1 if unset
+    pub rnext: i32,
+    /// 0-based position of the mate, or -1 if unset
+    pub pnext: i64,
+    /// Observed template length
+    pub tlen: i64,
+    /// Read bases, as ASCII. Empty if BWA wrote `*`.
+    pub seq: Vec<u8>,
+    /// Base qualities, without the Phred+33 offset. Empty if BWA wrote `*`.
+    pub qual: Vec<u8>,
+    /// The tab-separated aux tags of the SAM line, such as `NM:i:0\tAS:i:150`
+    pub aux: String,
+}
+
+impl SamRecordOwned {
+    /// Unmapped read (flag 0x4)
+    pub fn is_unmapped(&self) -> bool {
+        self.flag & 0x4 != 0
+    }
+
+    /// Reverse strand (flag 0x10)
+    pub fn is_reverse(&self) -> bool {
+        self.flag & 0x10 != 0
+    }
+
+    /// Secondary alignment (flag 0x100)
+    pub fn is_secondary(&self) -> bool {
+        self.flag & 0x100 != 0
+    }
+
+    /// Supplementary alignment (flag 0x800)
+    pub fn is_supplementary(&self) -> bool {
+        self.flag & 0x800 != 0
+    }
+}
+
+/// Convert BWA's SAM output for a read to `SamRecordOwned`s, adding an `RG` tag to
+/// each if `read_group` is set
+pub fn records_from_sam(
+    contig_tids: &HashMap<Vec<u8>, i32>,
+    sam: &[u8],
+    read_group: Option<&str>,
+) -> Result<Vec<SamRecordOwned>, AlignError> {
+    let mut records = Vec::new();
+
+    for line in sam.split(|c| *c == b'\n') {
+        if line.is_empty() {
+            continue;
+        }
+        let mut record = parse_line(contig_tids, line)
+            .ok_or_else(|| AlignError::MalformedSam(String::from_utf8_lossy(line).into_owned()))?;
+        // an RG tag passed through from a read comment takes precedence
+        let has_rg = record.aux.split('\t').any(|tag| tag.starts_with("RG:"));
+        if let (Some(rg), false) = (read_group, has_rg) {
+            if !record.aux.is_empty() {
+                record.aux.push('\t');
+            }
+            record.aux.push_str("RG:Z:");
+            record.aux.push_str(rg);
+        }
+        records.push(record);
+    }
+
+    // BWA always writes a record for each read, even if it's unmapped
+    if records.is_empty() {
+        return Err(AlignError::NoOutput);
+    }
+
+    Ok(records)
+}
+
+fn parse_line(contig_tids: &HashMap<Vec<u8>, i32>, line: &[u8]) -> Option<SamRecordOwned> {
+    let fields: Vec<&[u8]> = line.splitn(12, |c| *c == b'\t').collect();
+    if fields.len() < 11 {
+        return None;
+    }
+
+    let tid = lookup_tid(contig_tids, fields[2])?;
+    let rnext = match fields[6] {
+        b"=" => tid,
+        rnext => lookup_tid(contig_tids, rnext)?,
+    };
+    let seq = match fields[9] {
+        b"*" => Vec::new(),
+        seq => seq.to_vec(),
+    };
+    let qual = match fields[10] {
+        b"*" => Vec::new(),
+        qual if qual.len() == seq.len() && qual.iter().all(|&q| q >= 33) => {
+            qual.iter().map(|&q| q - 33).collect()
+        }
+        _ => return None,
+    };
+    let aux = match fields.get(11) {
+        Some(aux) => str::from_utf8(aux).ok()?.to_string(),
+        None => String::new(),
+    };
+
+    Some(SamRecordOwned {
+        qname: fields[0].to_vec(),
+        flag: parse_num(fields[1])?,
+        tid,
+        pos: parse_num::<i64>(fields[3])? - 1,
+        mapq: parse_num(fields[4])?,
+        cigar: str::from_utf8(fields[5]).ok()?.to_string(),
+        rnext,
+        pnext: parse_num::<i64>(fields[7])? - 1,
+        tlen: parse_num(fields[8])?,
+        seq,
+        qual,
+        aux,
+    })
+}
+
+pub fn parse_num<T: FromStr>(field: &[u8]) -> Option<T> {
+    str::from_utf8(field).ok()?.parse().ok()
+}
+
+pub fn lookup_tid(contig_tids: &HashMap<Vec<u8>, i32>, name: &[u8]) -> Option<i32> {
+    match name {
+        b"*" => Some(-1),
+        name => contig_tids.get(name).cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastq::FastqPairReader;
+    use BwaAligner;
+
+    #[test]
+    fn parse_sam_lines() {
+        let mut contig_tids = HashMap::new();
+        contig_tids.insert(b"chr".to_vec(), 1);
+
+        let sam = b"r1\t99\tchr\t101\t60\t4M\t=\t201\t104\tACGT\tII#I\tNM:i:0\tAS:i:4\n\
+                    r2\t4\t*\t0\t0\t*\t*\t0\t0\tAC\t*\n";
+        let recs = records_from_sam(&contig_tids, sam, Some("rg1")).unwrap();
+
+        assert_eq!(
+            recs[0],
+            SamRecordOwned {
+                qname: b"r1".to_vec(),
+                flag: 99,
+                tid: 1,
+                pos: 100,
+                mapq: 60,
+                cigar: "4M".to_string(),
+                rnext: 1,
+                pnext: 200,
+                tlen: 104,
+                seq: b"ACGT".to_vec(),
+                qual: vec![40, 40, 2, 40],
+                aux: "NM:i:0\tAS:i:4\tRG:Z:rg1".to_string(),
+            }
+        );
+        assert!(recs[1].is_unmapped());
+        assert_eq!((recs[1].tid, recs[1].pos, recs[1].rnext), (-1, -1, -1));
+        assert_eq!(recs[1].qual, b"");
+        assert_eq!(recs[1].aux, "RG:Z:rg1");
+
+        let sam = b"r1\t4\t*\t0\t0\t*\t*\t0\t0\tAC\t*\tRG:Z:x\n";
+        let recs = records_from_sam(&contig_tids, sam, Some("rg1")).unwrap();
+        assert_eq!(recs[0].aux, "RG:Z:x");
+
+        let bad: &[&[u8]] = &[
+            b"r1\t0\tchrX\t1\t60\t4M\t*\t0\t0\tACGT\tIIII",
+            b"r1\t0\tchr\t1",
+        ];
+        for sam in bad {
+            match records_from_sam(&contig_tids, sam, None) {
+                Err(AlignError::MalformedSam(_)) => (),
+                res => panic!("expected malformed SAM error, got {:?}", res),
+            }
+        }
+        match records_from_sam(&contig_tids, b"", None) {
+            Err(AlignError::NoOutput) => (),
+            res => panic!("expected no output error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn align_raw_positions() {
+        let bwa = BwaAligner::from_path("tests/test_ref.fa").unwrap();
+        let pairs: Vec<_> = FastqPairReader::from_paths("tests/pair_1.fq.gz", "tests/pair_2.fq.gz")
+            .unwrap()
+            .map(|pair| pair.unwrap())
+            .collect();
+        assert_eq!(pairs.len(), 2);
+
+        let mut positions = Vec::new();
+        for (r1, r2) in &pairs {
+            let (recs1, recs2) = bwa
+                .align_read_pair_raw(&r1.name, &r1.seq, &r1.qual, &r2.seq, &r2.qual)
+                .unwrap();
+            for rec in recs1.iter().chain(&recs2) {
+                assert_eq!(rec.qname, r1.name);
+                assert!(!rec.is_unmapped());
+                positions.push((rec.tid, rec.pos, rec.is_supplementary()));
+            }
+        }
+
+        assert!(positions.contains(&(1, 727806, false)));
+        assert!(positions.contains(&(1, 931375, false)));
+        assert!(positions.contains(&(1, 932605, true)));
+        assert!(positions.contains(&(1, 932937, false)));
+    }
+
+    #[cfg(feature = "htslib")]
+    #[test]
+    fn raw_matches_records() {
+        let bwa = BwaAligner::from_path("tests/test_ref.fa").unwrap();
+        let pairs =
+            FastqPairReader::from_paths("tests/pair_1.fq.gz", "tests/pair_2.fq.gz").unwrap();
+
+        for pair in pairs {
+            let (r1, r2) = pair.unwrap();
+            let (recs1, recs2) = bwa
+                .align_read_pair(&r1.name, &r1.seq, &r1.qual, &r2.seq, &r2.qual)
+                .unwrap();
+            let (raw1, raw2) = bwa
+                .align_read_pair_raw(&r1.name, &r1.seq, &r1.qual, &r2.seq, &r2.qual)
+                .unwrap();
+
+            assert_eq!(recs1.len() + recs2.len(), raw1.len() + raw2.len());
+            for (rec, raw) in recs1.iter().chain(&recs2).zip(raw1.iter().chain(&raw2)) {
+                assert_eq!(rec.qname(), &raw.qname[..]);
+                assert_eq!(rec.flags(), raw.flag);
+                assert_eq!((rec.tid(), rec.pos()), (raw.tid, raw.pos));
+                assert_eq!((rec.mtid(), rec.mpos()), (raw.rnext, raw.pnext));
+                assert_eq!(rec.mapq(), raw.mapq);
+                assert_eq!(rec.insert_size(), raw.tlen);
+                assert_eq!(rec.cigar().to_string(), raw.cigar);
+                assert_eq!(rec.seq().as_bytes(), raw.seq);
+                assert_eq!(rec.qual(), &raw.qual[..]);
+            }
+        }
+    }
+}
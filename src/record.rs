@@ -0,0 +1,283 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+//! Helpers for extracting BWA-specific information from the BAM records
+//! returned by `BwaAligner`.
+//!
+//! BWA tags every mapped record with its edit distance (`NM`), mismatch string (`MD`),
+//! alignment score (`AS`) and the score of the best suboptimal alignment (`XS`), all
+//! computed against the loaded reference. Chimeric and repetitive reads may also have
+//! `SA` and `XA` tags.
+
+use std::convert::TryFrom;
+
+use rust_htslib::bam::record::{Aux, Cigar, CigarString, Record};
+
+use BwaReference;
+
+/// Get the alignment score of a record from its `AS` tag. Returns `None` if the
+/// tag is missing or isn't an integer, and for unmapped reads, which BWA tags
+/// with a placeholder score of 0.
+pub fn alignment_score(rec: &Record) -> Option<i32> {
+    int_tag(rec, b"AS")
+}
+
+/// Get the score of the best suboptimal alignment of a record's read from its `XS` tag,
+/// which is 0 if BWA found no other alignment. Returns `None` as for `alignment_score`.
+pub fn suboptimal_score(rec: &Record) -> Option<i32> {
+    int_tag(rec, b"XS")
+}
+
+/// Get the edit distance of a record to the reference from its `NM` tag. Returns `None`
+/// if the tag is missing or isn't an integer, and for unmapped reads.
+pub fn edit_distance(rec: &Record) -> Option<u32> {
+    int_tag(rec, b"NM").map(|nm| nm as u32)
+}
+
+/// Get the mismatch string of a record from its `MD` tag, giving the reference bases at
+/// mismatches and deletions, so pileups can be made without the reference. Returns `None`
+/// if the tag is missing and for unmapped reads.
+pub fn mismatch_string(rec: &Record) -> Option<&str> {
+    if rec.is_unmapped() {
+        return None;
+    }
+
+    match rec.aux(b"MD") {
+        Ok(Aux::String(md)) => Some(md),
+        _ => None,
+    }
+}
+
+/// Get an integer tag of a mapped record, which htslib may store as any integer type
+fn int_tag(rec: &Record, tag: &[u8]) -> Option<i32> {
+    if rec.is_unmapped() {
+        return None;
+    }
+
+    match rec.aux(tag) {
+        Ok(Aux::I8(v)) => Some(v as i32),
+        Ok(Aux::U8(v)) => Some(v as i32),
+        Ok(Aux::I16(v)) => Some(v as i32),
+        Ok(Aux::U16(v)) => Some(v as i32),
+        Ok(Aux::I32(v)) => Some(v),
+        Ok(Aux::U32(v)) if v <= i32::MAX as u32 => Some(v as i32),
+        _ => None,
+    }
+}
+
+/// Get the reference span of a record as 1-based, inclusive start and end positions, as
+/// used in VCF and GFF files. The end is computed from the CIGAR, so soft and hard clipped
+/// bases aren't counted, and deletions are. Returns `None` for unmapped reads.
+pub fn alignment_span(rec: &Record) -> Option<(i64, i64)> {
+    if rec.is_unmapped() {
+        return None;
+    }
+    Some((rec.pos() + 1, rec.cigar().end_pos()))
+}
+
+/// Get the template length (TLEN) BWA computed for a paired record: the distance from
+/// the leftmost to the rightmost mapped base of the pair, positive for the leftmost read
+/// and negative for its mate. Returns `None` if either read is unmapped or the reads map
+/// to different contigs, where BWA writes a placeholder length of 0. Whether the pair was
+/// aligned as a proper pair is given by `Record::is_proper_pair`.
+pub fn template_length(rec: &Record) -> Option<i64> {
+    if rec.is_unmapped() || rec.is_mate_unmapped() || rec.tid() != rec.mtid() {
+        return None;
+    }
+    Some(rec.insert_size())
+}
+
+/// An alternative or supplementary alignment of a read, parsed from its `XA` or `SA` tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct AltHit {
+    /// Reference sequence id
+    pub tid: i32,
+    /// 0-based leftmost position
+    pub pos: i64,
+    pub is_reverse: bool,
+    pub cigar: CigarString,
+    /// Mapping quality, which is only given for the hits in an `SA` tag
+    pub mapq: Option<u8>,
+    /// Edit distance to the reference
+    pub nm: u32,
+}
+
+/// Error parsing an `XA` or `SA` tag
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct TagParseError(String);
+
+/// Parse the alternative hits in the `XA` tag of a record, formatted as
+/// `contig,{+|-}pos,cigar,NM;` by BWA. Returns an empty list if the tag is missing.
+pub fn parse_xa(rec: &Record, reference: &BwaReference) -> Result<Vec<AltHit>, TagParseError> {
+    parse_hits(rec, reference, b"XA", 4, |tid, fields| {
+        let (strand, pos) = fields[1].split_at(1.min(fields[1].len()));
+        Some(AltHit {
+            tid,
+            pos: pos.parse::<i64>().ok()? - 1,
+            is_reverse: parse_strand(strand)?,
+            cigar: CigarString::try_from(fields[2]).ok()?,
+            mapq: None,
+            nm: fields[3].parse().ok()?,
+        })
+    })
+}
+
+/// Parse the other parts of a chimeric alignment from the `SA` tag of a record, formatted
+/// as `contig,pos,strand,cigar,mapq,NM;` by BWA. Returns an empty list if the tag is missing.
+pub fn parse_sa(rec: &Record, reference: &BwaReference) -> Result<Vec<AltHit>, TagParseError> {
+    parse_hits(rec, reference, b"SA", 6, |tid, fields| {
+        Some(AltHit {
+            tid,
+            pos: fields[1].parse::<i64>().ok()? - 1,
+            is_reverse: parse_strand(fields[2])?,
+            cigar: CigarString::try_from(fields[3]).ok()?,
+            mapq: Some(fields[4].parse().ok()?),
+            nm: fields[5].parse().ok()?,
+        })
+    })
+}
+
+fn parse_strand(strand: &str) -> Option<bool> {
+    match strand {
+        "+" => Some(false),
+        "-" => Some(true),
+        _ => None,
+    }
+}
+
+/// Parse the `;`-separated hits of a tag, each with `n_fields` comma-separated fields
+/// starting with the contig name
+fn parse_hits<F>(
+    rec: &Record,
+    reference: &BwaReference,
+    tag: &[u8],
+    n_fields: usize,
+    parse_hit: F,
+) -> Result<Vec<AltHit>, TagParseError>
+where
+    F: Fn(i32, &[&str]) -> Option<AltHit>,
+{
+    let tag_name = String::from_utf8_lossy(tag);
+    let value = match rec.aux(tag) {
+        Ok(Aux::String(value)) => value,
+        Ok(_) => return Err(TagParseError(format!("{} tag isn't a string", tag_name))),
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let mut hits = Vec::new();
+    for hit in value.split(';').filter(|hit| !hit.is_empty()) {
+        let fields: Vec<&str> = hit.split(',').collect();
+        if fields.len() != n_fields {
+            return Err(TagParseError(format!(
+                "malformed {} tag entry: {}",
+                tag_name, hit
+            )));
+        }
+
+        let tid = reference.contig_tid(fields[0]).ok_or_else(|| {
+            TagParseError(format!("unknown contig {} in {} tag", fields[0], tag_name))
+        })?;
+        let hit = parse_hit(tid, &fields)
+            .ok_or_else(|| TagParseError(format!("malformed {} tag entry: {}", tag_name, hit)))?;
+        hits.push(hit);
+    }
+
+    Ok(hits)
+}
+
+/// An owned copy of an aligned record. Unlike a `Record`, it holds no reference to a
+/// `HeaderView`, so it's `Send` and `'static`, and can be passed between threads or
+/// through channels. Build one from a `Record` with `OwnedAlignment::from`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedAlignment {
+    pub qname: Vec<u8>,
+    pub flags: u16,
+    pub tid: i32,
+    /// 0-based leftmost position
+    pub pos: i64,
+    pub mapq: u8,
+    pub cigar: Vec<Cigar>,
+    pub mtid: i32,
+    pub mpos: i64,
+    pub insert_size: i64,
+    /// Read bases, as ASCII
+    pub seq: Vec<u8>,
+    /// Base qualities, without the Phred+33 offset
+    pub qual: Vec<u8>,
+    /// Aux tags, in the order they're stored in the record
+    pub aux: Vec<([u8; 2], AuxValue)>,
+}
+
+impl OwnedAlignment {
+    /// Get the value of an aux tag, or `None` if the tag is missing
+    pub fn aux(&self, tag: &[u8]) -> Option<&AuxValue> {
+        self.aux
+            .iter()
+            .find(|&&(t, _)| &t[..] == tag)
+            .map(|(_, value)| value)
+    }
+}
+
+/// The value of an aux tag of an `OwnedAlignment`. Integers of any width are stored as
+/// `i64`, and floats as `f64`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuxValue {
+    Char(u8),
+    Int(i64),
+    Float(f64),
+    String(String),
+    HexByteArray(String),
+    IntArray(Vec<i64>),
+    FloatArray(Vec<f32>),
+}
+
+impl<'a> From<Aux<'a>> for AuxValue {
+    fn from(aux: Aux<'a>) -> AuxValue {
+        match aux {
+            Aux::Char(v) => AuxValue::Char(v),
+            Aux::I8(v) => AuxValue::Int(v.into()),
+            Aux::U8(v) => AuxValue::Int(v.into()),
+            Aux::I16(v) => AuxValue::Int(v.into()),
+            Aux::U16(v) => AuxValue::Int(v.into()),
+            Aux::I32(v) => AuxValue::Int(v.into()),
+            Aux::U32(v) => AuxValue::Int(v.into()),
+            Aux::Float(v) => AuxValue::Float(v.into()),
+            Aux::Double(v) => AuxValue::Float(v),
+            Aux::String(v) => AuxValue::String(v.to_string()),
+            Aux::HexByteArray(v) => AuxValue::HexByteArray(v.to_string()),
+            Aux::ArrayI8(a) => AuxValue::IntArray(a.iter().map(i64::from).collect()),
+            Aux::ArrayU8(a) => AuxValue::IntArray(a.iter().map(i64::from).collect()),
+            Aux::ArrayI16(a) => AuxValue::IntArray(a.iter().map(i64::from).collect()),
+            Aux::ArrayU16(a) => AuxValue::IntArray(a.iter().map(i64::from).collect()),
+            Aux::ArrayI32(a) => AuxValue::IntArray(a.iter().map(i64::from).collect()),
+            Aux::ArrayU32(a) => AuxValue::IntArray(a.iter().map(i64::from).collect()),
+            Aux::ArrayFloat(a) => AuxValue::FloatArray(a.iter().collect()),
+        }
+    }
+}
+
+impl From<&Record> for OwnedAlignment {
+    fn from(rec: &Record) -> OwnedAlignment {
+        // the aux data of the records built by BwaAligner is always well-formed
+        let aux = rec
+            .aux_iter()
+            .filter_map(Result::ok)
+            .map(|(tag, value)| ([tag[0], tag[1]], AuxValue::from(value)))
+            .collect();
+
+        OwnedAlignment {
+            qname: rec.qname().to_vec(),
+            flags: rec.flags(),
+            tid: rec.tid(),
+            pos: rec.pos(),
+            mapq: rec.mapq(),
+            cigar: rec.cigar().iter().cloned().collect(),
+            mtid: rec.mtid(),
+            mpos: rec.mpos(),
+            insert_size: rec.insert_size(),
+            seq: rec.seq().as_bytes(),
+            qual: rec.qual().to_vec(),
+            aux,
+        }
+    }
+}
@@ -0,0 +1,369 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+//! Conversion of the SAM lines written by BWA to htslib `Record`s. htslib's own SAM
+//! parser needs a `HeaderView` to look up contig names, which can't be shared between
+//! threads, so the contig tids are looked up here instead and the record is built
+//! directly, matching what `Record::from_sam` would produce.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str::{self, FromStr};
+
+use rust_htslib::bam::record::{Aux, Cigar, CigarString, Record};
+use rust_htslib::errors::Error;
+
+use record::AuxValue;
+use AlignError;
+
+const BAM_FUNMAP: u16 = 0x4;
+
+/// Convert one line of SAM output from BWA to a `Record`
+pub fn record_from_sam(
+    contig_tids: &HashMap<Vec<u8>, i32>,
+    line: &[u8],
+) -> Result<Record, AlignError> {
+    parse_line(contig_tids, line).map_err(|source| AlignError::SamParse {
+        line: String::from_utf8_lossy(line).into_owned(),
+        source,
+    })
+}
+
+fn parse_line(contig_tids: &HashMap<Vec<u8>, i32>, line: &[u8]) -> Result<Record, Error> {
+    let bad = || Error::BamParseSAM {
+        rec: String::from_utf8_lossy(line).into_owned(),
+    };
+
+    let fields: Vec<&[u8]> = line.split(|c| *c == b'\t').collect();
+    if fields.len() < 11 || fields[0].len() > 254 {
+        return Err(bad());
+    }
+
+    let qname = fields[0];
+    let flags: u16 = parse_num(fields[1]).ok_or_else(bad)?;
+    let tid = lookup_tid(contig_tids, fields[2]).ok_or_else(bad)?;
+    let pos = parse_num::<i64>(fields[3]).ok_or_else(bad)? - 1;
+    let mapq: u8 = parse_num(fields[4]).ok_or_else(bad)?;
+    let cigar = match fields[5] {
+        b"*" => None,
+        cigar => Some(CigarString::try_from(cigar)?),
+    };
+    let mtid = match fields[6] {
+        b"=" => tid,
+        rnext => lookup_tid(contig_tids, rnext).ok_or_else(bad)?,
+    };
+    let mpos = parse_num::<i64>(fields[7]).ok_or_else(bad)? - 1;
+    let insert_size: i64 = parse_num(fields[8]).ok_or_else(bad)?;
+
+    let seq = match fields[9] {
+        b"*" => &[][..],
+        seq => seq,
+    };
+    // '*' qualities are stored as 0xff
+    let qual: Vec<u8> = match fields[10] {
+        b"*" => vec![0xff; seq.len()],
+        qual if qual.len() == seq.len() && qual.iter().all(|&q| q >= 33) => {
+            qual.iter().map(|&q| q - 33).collect()
+        }
+        _ => return Err(bad()),
+    };
+
+    // htslib treats unmapped reads as covering a single reference base when computing the bin
+    let ref_len = match cigar {
+        Some(ref cigar) if flags & BAM_FUNMAP == 0 => cigar.iter().map(cigar_ref_len).sum(),
+        _ => 0,
+    };
+
+    let mut record = Record::new();
+    record.set(qname, cigar.as_ref(), seq, &qual);
+    record.set_tid(tid);
+    record.set_pos(pos);
+    record.set_bin(reg2bin(pos, pos + ref_len.max(1)));
+    record.set_mapq(mapq);
+    record.set_flags(flags);
+    record.set_mtid(mtid);
+    record.set_mpos(mpos);
+    record.set_insert_size(insert_size);
+
+    for field in &fields[11..] {
+        if field.len() < 5 || field[2] != b':' || field[4] != b':' {
+            return Err(bad());
+        }
+        let value = str::from_utf8(&field[5..]).map_err(|_| bad())?;
+
+        // arrays are borrowed by their `Aux`, so are pushed as soon as they're parsed
+        if field[3] == b'B' {
+            push_array_aux(&mut record, &field[..2], value, bad)?;
+            continue;
+        }
+
+        let aux = match field[3] {
+            b'A' if value.len() == 1 => Aux::Char(value.as_bytes()[0]),
+            b'i' => value.parse().ok().and_then(int_aux).ok_or_else(bad)?,
+            b'f' => Aux::Float(value.parse().map_err(|_| bad())?),
+            b'Z' => Aux::String(value),
+            b'H' => Aux::HexByteArray(value),
+            _ => return Err(bad()),
+        };
+        record.push_aux(&field[..2], aux)?;
+    }
+
+    Ok(record)
+}
+
+/// Add a `B` array aux tag with a value such as `C,200,10` to the record
+fn push_array_aux<F: Fn() -> Error>(
+    record: &mut Record,
+    tag: &[u8],
+    value: &str,
+    bad: F,
+) -> Result<(), Error> {
+    let (subtype, values) = match value.find(',') {
+        Some(i) => (&value[..i], &value[i + 1..]),
+        None => (value, ""),
+    };
+
+    match subtype {
+        "c" => record.push_aux(
+            tag,
+            Aux::ArrayI8((&parse_array::<i8>(values).ok_or_else(&bad)?).into()),
+        ),
+        "C" => record.push_aux(
+            tag,
+            Aux::ArrayU8((&parse_array::<u8>(values).ok_or_else(&bad)?).into()),
+        ),
+        "s" => record.push_aux(
+            tag,
+            Aux::ArrayI16((&parse_array::<i16>(values).ok_or_else(&bad)?).into()),
+        ),
+        "S" => record.push_aux(
+            tag,
+            Aux::ArrayU16((&parse_array::<u16>(values).ok_or_else(&bad)?).into()),
+        ),
+        "i" => record.push_aux(
+            tag,
+            Aux::ArrayI32((&parse_array::<i32>(values).ok_or_else(&bad)?).into()),
+        ),
+        "I" => record.push_aux(
+            tag,
+            Aux::ArrayU32((&parse_array::<u32>(values).ok_or_else(&bad)?).into()),
+        ),
+        "f" => record.push_aux(
+            tag,
+            Aux::ArrayFloat((&parse_array::<f32>(values).ok_or_else(&bad)?).into()),
+        ),
+        _ => Err(bad()),
+    }
+}
+
+/// Parse the comma-separated values of a `B` array
+fn parse_array<T: FromStr>(values: &str) -> Option<Vec<T>> {
+    if values.is_empty() {
+        return Some(Vec::new());
+    }
+    values.split(',').map(|v| v.parse().ok()).collect()
+}
+
+/// Add the aux tags to each of the records, storing integers in the smallest type that
+/// holds them
+pub fn push_tags(records: &mut [Record], tags: &[([u8; 2], AuxValue)]) -> Result<(), AlignError> {
+    for record in records {
+        for (tag, value) in tags {
+            push_tag(record, tag, value)?;
+        }
+    }
+    Ok(())
+}
+
+fn push_tag(record: &mut Record, tag: &[u8], value: &AuxValue) -> Result<(), AlignError> {
+    let invalid = |msg: String| AlignError::InvalidTag {
+        tag: String::from_utf8_lossy(tag).into_owned(),
+        msg,
+    };
+
+    let res = match *value {
+        AuxValue::Char(c) => record.push_aux(tag, Aux::Char(c)),
+        AuxValue::Int(v) => {
+            let aux = int_aux(v).ok_or_else(|| invalid(format!("{} is out of range", v)))?;
+            record.push_aux(tag, aux)
+        }
+        AuxValue::Float(v) => record.push_aux(tag, Aux::Float(v as f32)),
+        AuxValue::String(ref v) => record.push_aux(tag, Aux::String(v)),
+        AuxValue::HexByteArray(ref v) => record.push_aux(tag, Aux::HexByteArray(v)),
+        AuxValue::IntArray(ref v) => {
+            if let Some(a) = int_array::<u8>(v) {
+                record.push_aux(tag, Aux::ArrayU8((&a).into()))
+            } else if let Some(a) = int_array::<i8>(v) {
+                record.push_aux(tag, Aux::ArrayI8((&a).into()))
+            } else if let Some(a) = int_array::<u16>(v) {
+                record.push_aux(tag, Aux::ArrayU16((&a).into()))
+            } else if let Some(a) = int_array::<i16>(v) {
+                record.push_aux(tag, Aux::ArrayI16((&a).into()))
+            } else if let Some(a) = int_array::<u32>(v) {
+                record.push_aux(tag, Aux::ArrayU32((&a).into()))
+            } else if let Some(a) = int_array::<i32>(v) {
+                record.push_aux(tag, Aux::ArrayI32((&a).into()))
+            } else {
+                return Err(invalid("array values are out of range".to_string()));
+            }
+        }
+        AuxValue::FloatArray(ref v) => record.push_aux(tag, Aux::ArrayFloat(v.into())),
+    };
+    res.map_err(|e| invalid(e.to_string()))
+}
+
+/// Convert the values of an integer array, if they all fit in `T`
+fn int_array<T: TryFrom<i64>>(values: &[i64]) -> Option<Vec<T>> {
+    values.iter().map(|&v| T::try_from(v).ok()).collect()
+}
+
+fn parse_num<T: FromStr>(field: &[u8]) -> Option<T> {
+    str::from_utf8(field).ok()?.parse().ok()
+}
+
+fn lookup_tid(contig_tids: &HashMap<Vec<u8>, i32>, name: &[u8]) -> Option<i32> {
+    match name {
+        b"*" => Some(-1),
+        name => contig_tids.get(name).cloned(),
+    }
+}
+
+fn cigar_ref_len(op: &Cigar) -> i64 {
+    match *op {
+        Cigar::Match(l) | Cigar::Del(l) | Cigar::RefSkip(l) | Cigar::Equal(l) | Cigar::Diff(l) => {
+            i64::from(l)
+        }
+        _ => 0,
+    }
+}
+
+/// The smallest integer aux type that holds `value`, as chosen by htslib's SAM parser
+fn int_aux(value: i64) -> Option<Aux<'static>> {
+    if value < 0 {
+        if value >= i64::from(i8::MIN) {
+            Some(Aux::I8(value as i8))
+        } else if value >= i64::from(i16::MIN) {
+            Some(Aux::I16(value as i16))
+        } else if value >= i64::from(i32::MIN) {
+            Some(Aux::I32(value as i32))
+        } else {
+            None
+        }
+    } else if value <= i64::from(u8::MAX) {
+        Some(Aux::U8(value as u8))
+    } else if value <= i64::from(u16::MAX) {
+        Some(Aux::U16(value as u16))
+    } else if value <= i64::from(u32::MAX) {
+        Some(Aux::U32(value as u32))
+    } else {
+        None
+    }
+}
+
+/// The BAI bin of the half-open interval `[beg, end)`, as computed by htslib's `hts_reg2bin`
+fn reg2bin(beg: i64, end: i64) -> u16 {
+    let end = end - 1;
+    let mut shift = 14;
+    let mut offset = ((1 << 15) - 1) / 7;
+    for level in (1..6).rev() {
+        if beg >> shift == end >> shift {
+            return (offset + (beg >> shift)) as u16;
+        }
+        shift += 3;
+        offset -= 1 << (3 * (level - 1));
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bins() {
+        // values from the SAM spec's reg2bin
+        assert_eq!(reg2bin(0, 1), 4681);
+        assert_eq!(reg2bin(16383, 16384), 4681);
+        assert_eq!(reg2bin(16383, 16385), 585);
+        assert_eq!(reg2bin(-1, 0), 4680);
+        assert_eq!(reg2bin(0, 1 << 29), 0);
+    }
+
+    #[test]
+    fn int_aux_types() {
+        assert_eq!(int_aux(0), Some(Aux::U8(0)));
+        assert_eq!(int_aux(300), Some(Aux::U16(300)));
+        assert_eq!(int_aux(70000), Some(Aux::U32(70000)));
+        assert_eq!(int_aux(-1), Some(Aux::I8(-1)));
+        assert_eq!(int_aux(-300), Some(Aux::I16(-300)));
+        assert_eq!(int_aux(-70000), Some(Aux::I32(-70000)));
+        assert_eq!(int_aux(1 << 40), None);
+    }
+
+    #[test]
+    fn push_tag_types() {
+        let mut records = vec![Record::new()];
+        let tags = [
+            (*b"MM", AuxValue::String("C+m,0,2;".to_string())),
+            (*b"ML", AuxValue::IntArray(vec![200, 10])),
+            (*b"XN", AuxValue::IntArray(vec![-1, 300])),
+            (*b"XI", AuxValue::Int(-5)),
+        ];
+        push_tags(&mut records, &tags).unwrap();
+
+        let rec = &records[0];
+        assert_eq!(rec.aux(b"MM").unwrap(), Aux::String("C+m,0,2;"));
+        match rec.aux(b"ML") {
+            Ok(Aux::ArrayU8(a)) => assert_eq!(a.iter().collect::<Vec<_>>(), [200, 10]),
+            aux => panic!("expected u8 array, got {:?}", aux),
+        }
+        match rec.aux(b"XN") {
+            Ok(Aux::ArrayI16(a)) => assert_eq!(a.iter().collect::<Vec<_>>(), [-1, 300]),
+            aux => panic!("expected i16 array, got {:?}", aux),
+        }
+        assert_eq!(rec.aux(b"XI").unwrap(), Aux::I8(-5));
+
+        let tags = [(*b"XL", AuxValue::Int(1 << 40))];
+        match push_tags(&mut records, &tags) {
+            Err(AlignError::InvalidTag { tag, .. }) => assert_eq!(tag, "XL"),
+            res => panic!("expected invalid tag error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn array_tags() {
+        let mut contig_tids = HashMap::new();
+        contig_tids.insert(b"chr".to_vec(), 0);
+        let line = b"r1\t0\tchr\t1\t60\t4M\t*\t0\t0\tACGT\tIIII\t\
+                     ML:B:C,200,10\tXS:B:s,-1,300\tXF:B:f,0.5\tXE:B:I";
+        let rec = record_from_sam(&contig_tids, line).unwrap();
+
+        match rec.aux(b"ML") {
+            Ok(Aux::ArrayU8(a)) => assert_eq!(a.iter().collect::<Vec<_>>(), [200, 10]),
+            aux => panic!("expected u8 array, got {:?}", aux),
+        }
+        match rec.aux(b"XS") {
+            Ok(Aux::ArrayI16(a)) => assert_eq!(a.iter().collect::<Vec<_>>(), [-1, 300]),
+            aux => panic!("expected i16 array, got {:?}", aux),
+        }
+        match rec.aux(b"XF") {
+            Ok(Aux::ArrayFloat(a)) => assert_eq!(a.iter().collect::<Vec<_>>(), [0.5]),
+            aux => panic!("expected float array, got {:?}", aux),
+        }
+        match rec.aux(b"XE") {
+            Ok(Aux::ArrayU32(a)) => assert_eq!(a.len(), 0),
+            aux => panic!("expected u32 array, got {:?}", aux),
+        }
+
+        for tag in &["ML:B:C,300", "ML:B:x,1", "ML:B:C,1,,2"] {
+            let line = [
+                &b"r1\t0\tchr\t1\t60\t4M\t*\t0\t0\tACGT\tIIII\t"[..],
+                tag.as_bytes(),
+            ]
+            .concat();
+            match record_from_sam(&contig_tids, &line) {
+                Err(AlignError::SamParse { .. }) => (),
+                res => panic!("expected SAM parse error, got {:?}", res),
+            }
+        }
+    }
+}